@@ -21,13 +21,26 @@
 ///   function must be in scope.
 ///
 /// * `let pattern = expression`: assign expression to pattern, as
-///   normal rust let.
+///   normal rust let. `let mut name: type = expression` is also
+///   accepted.
+///
+/// * `pattern =<<? expression, err_fn`: bind expression to pattern,
+///   mapping its error with `err_fn`. a `map_err_bind` function must
+///   be in scope.
 ///
 /// * `ign expression`: equivalent to `_ =<< expression`
 ///
 /// * `when expression`: filter on the monad. `ret` and `mzero`
 ///   functions must be in scope.
 ///
+/// * `ensure condition, error`: fail with `error`, only evaluated if
+///   the condition is false. an `ensure` function as `result::ensure`
+///   must be in scope.
+///
+/// * `for pattern in expression { (instr)* ; ret expr }`: equivalent
+///   to `pattern =<< expression; (instr)* ; ret expr`, the `for` block
+///   ending the `mdo!` block.
+///
 /// # Example
 ///
 /// ```
@@ -44,8 +57,33 @@
 ///     assert_eq!(l, vec![10, 10, 12, 12, 14, 14]);
 /// }
 /// ```
+///
+/// # Missing functions
+///
+/// As the functions are found by name, forgetting to import them gives
+/// errors such as ``cannot find function `bind` in this scope``. They
+/// must be imported from the module of the used monad, as
+/// `use mdo::option::{bind, ret, mzero};`.
 #[macro_export]
 macro_rules! mdo {
+    (
+        @for ($p: pat) ($( $e: tt )*) { $( $t: tt )* }
+    ) => (
+        bind($( $e )*, move |$p| mdo! { $( $t )* })
+    );
+
+    (
+        @for ($p: pat) ($( $e: tt )*) $next: tt $( $rest: tt )+
+    ) => (
+        mdo! { @for ($p) ($( $e )* $next) $( $rest )+ }
+    );
+
+    (
+        for $p: pat in $( $t: tt )+
+    ) => (
+        mdo! { @for ($p) () $( $t )+ }
+    );
+
     (
         let $p: pat = $e: expr ; $( $t: tt )*
     ) => (
@@ -58,6 +96,24 @@ macro_rules! mdo {
         { let $p: $ty = $e ; mdo! { $( $t )* } }
     );
 
+    (
+        let mut $p: ident : $ty: ty = $e: expr ; $( $t: tt )*
+    ) => (
+        { let mut $p: $ty = $e ; mdo! { $( $t )* } }
+    );
+
+    (
+        $p: pat =<<? $e: expr , $f: expr ; $( $t: tt )*
+    ) => (
+        map_err_bind($e, $f, move |$p| mdo! { $( $t )* } )
+    );
+
+    (
+        $p: ident : $ty: ty =<<? $e: expr , $f: expr ; $( $t: tt )*
+    ) => (
+        map_err_bind($e, $f, move |$p : $ty| mdo! { $( $t )* } )
+    );
+
     (
         $p: pat =<< $e: expr ; $( $t: tt )*
     ) => (
@@ -82,217 +138,3855 @@ macro_rules! mdo {
         bind(if $e { ret(()) } else { mzero() }, move |_| mdo! { $( $t )* })
     );
 
+    (
+        ensure $c: expr , $err: expr ; $( $t: tt )*
+    ) => (
+        bind(ensure($c, || $err), move |_| mdo! { $( $t )* })
+    );
+
     (
         ret $f: expr
     ) => (
         $f
+    );
+
+    () => (
+        compile_error!("missing `ret expression` at the end of the mdo! block")
     )
 }
 
-pub mod option {
-    //! Monadic functions for Option<T>
+/// Do notation for `Result` allowing the `?` operator at every point
+///
+/// The block is evaluated in an immediately invoked closure returning
+/// a `Result`, so `?` can be used in any expression, and each bind is
+/// desugared to `?`. No function needs to be in scope.
+///
+/// Syntax:
+/// `(instr)* ; ret expr`
+///
+/// instr can be:
+///
+/// * `pattern =<< expression`: bind expression to pattern, equivalent
+///   to `let pattern = expression?`.
+///
+/// * `let pattern = expression`: assign expression to pattern, as
+///   normal rust let.
+///
+/// * `ign expression`: equivalent to `_ =<< expression`
+///
+/// # Example
+///
+/// ```
+/// #[macro_use] extern crate mdo;
+/// fn main() {
+///     let r: Result<i32, std::num::ParseIntError> = try_m! {
+///         x: i32 =<< "5".parse();
+///         let y = "2".parse::<i32>()? * 2;
+///         ret Ok(x + y)
+///     };
+///     assert_eq!(r, Ok(9));
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_m {
+    (
+        @body let $p: pat = $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p = $e ; try_m! { @body $( $t )* } }
+    );
 
-    /// bind for Option<T>, equivalent to `m.and_then(f)`
-    pub fn bind<T, U, F: FnOnce(T) -> Option<U>>(m: Option<T>, f: F) -> Option<U> {
-        m.and_then(f)
-    }
+    (
+        @body let $p: ident : $ty: ty = $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p: $ty = $e ; try_m! { @body $( $t )* } }
+    );
 
-    /// return for Option<T>, equivalent to `Some(x)`
-    pub fn ret<T>(x: T) -> Option<T> {
-        Some(x)
-    }
+    (
+        @body $p: pat =<< $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p = $e? ; try_m! { @body $( $t )* } }
+    );
 
-    /// mzero for Option<T>, equivalent to `None`
-    pub fn mzero<T>() -> Option<T> {
-        None
-    }
-}
+    (
+        @body $p: ident : $ty: ty =<< $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p: $ty = $e? ; try_m! { @body $( $t )* } }
+    );
 
-pub mod result {
-    //! Monadic functions for Result<T, E>
+    (
+        @body ign $e: expr ; $( $t: tt )*
+    ) => (
+        { $e? ; try_m! { @body $( $t )* } }
+    );
 
-    /// bind for Result<T, E>, equivalent to `m.and_then(f)`
-    pub fn bind<T, E, U, F: FnOnce(T) -> Result<U, E>>(m: Result<T, E>, f: F) -> Result<U, E> {
-        m.and_then(f)
-    }
+    (
+        @body ret $f: expr
+    ) => (
+        $f
+    );
 
-    /// return for Result<T, E>, equivalent to `Ok(x)`
-    pub fn ret<T, E>(x: T) -> Result<T, E> {
-        Ok(x)
-    }
+    (
+        @body
+    ) => (
+        compile_error!("missing `ret expression` at the end of the try_m! block")
+    );
+
+    (
+        @body $( $t: tt )*
+    ) => (
+        compile_error!("unsupported instruction in the try_m! block")
+    );
+
+    (
+        $( $t: tt )*
+    ) => (
+        (|| -> ::std::result::Result<_, _> { try_m! { @body $( $t )* } })()
+    )
 }
 
-pub mod iter {
-    //! Monadic functions for Iterator<T>
+/// Monadic do notation using the `Monad` trait
+///
+/// Same syntax as `mdo!` without `when`, but binds call
+/// `Monad::bind` instead of a `bind` function in scope, thus working
+/// with any type implementing `mdo::monad::Monad`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use] extern crate mdo;
+/// fn main() {
+///     use mdo::monad::Monad;
+///     let l = mdo_m! {
+///         x =<< vec![1, 2];
+///         y =<< vec![10, 20];
+///         ret Monad::ret(x + y)
+///     };
+///     assert_eq!(l, vec![11, 21, 12, 22]);
+/// }
+/// ```
+///
+/// # Limitation
+///
+/// As `Vec` calls the closures several times, `Monad::bind` takes an
+/// `FnMut` closure for every monad, even `Option` and `Result`. A
+/// captured value thus can't be moved out in a later step, which
+/// `mdo!` accepts with `option::bind`:
+///
+/// ```compile_fail,E0507
+/// #[macro_use] extern crate mdo;
+/// fn main() {
+///     use mdo::monad::Monad;
+///     let s = String::from("a");
+///     let r = mdo_m! {
+///         x =<< Some(1);
+///         y =<< Some(s);
+///         ret Monad::ret(format!("{}{}", x, y))
+///     };
+///     assert_eq!(r, Some("1a".to_string()));
+/// }
+/// ```
+///
+/// Clone the value instead, as with `y =<< Some(s.clone());`.
+#[macro_export]
+macro_rules! mdo_m {
+    (
+        let $p: pat = $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p = $e ; mdo_m! { $( $t )* } }
+    );
 
-    use std::option;
-    use std::iter::FlatMap;
+    (
+        let $p: ident : $ty: ty = $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p: $ty = $e ; mdo_m! { $( $t )* } }
+    );
 
-    /// bind for Iterator<T, E>, equivalent to `m.flat_map(f)`
-    pub fn bind<I, U, F>(m: I, f: F) -> FlatMap<I, U, F>
-    where I: Iterator, U: Iterator, F: FnMut(<I as Iterator>::Item) -> U {
-        m.flat_map(f)
-    }
+    (
+        $p: pat =<< $e: expr ; $( $t: tt )*
+    ) => (
+        $crate::monad::Monad::bind($e, move |$p| mdo_m! { $( $t )* } )
+    );
 
-    /// return for Iterator<T>, an iterator with one value.
-    pub fn ret<T>(x: T) -> option::IntoIter<T> {
-        Some(x).into_iter()
-    }
+    (
+        $p: ident : $ty: ty =<< $e: expr ; $( $t: tt )*
+    ) => (
+        $crate::monad::Monad::bind($e, move |$p : $ty| mdo_m! { $( $t )* } )
+    );
 
-    /// mzero for Iterator<T>, an empty iterator.
-    pub fn mzero<T>() -> option::IntoIter<T> {
-        None.into_iter()
-    }
+    (
+        ign $e: expr ; $( $t: tt )*
+    ) => (
+        $crate::monad::Monad::bind($e, move |_| mdo_m! { $( $t )* })
+    );
+
+    (
+        ret $f: expr
+    ) => (
+        $f
+    )
 }
 
-#[cfg(test)]
-mod tests {
+/// Applicative lifting of a pure function over independent monadic
+/// values
+///
+/// `mdo_map!(f; a, b, c)` evaluates all the monadic values, then
+/// applies `f` to their contents, without any value depending on a
+/// previous one. For `Option` and `Result`, this is `Some`/`Ok` only if
+/// all the values are; for iterators, `f` is applied on the cartesian
+/// product. `bind` and `ret` functions must be in scope, and `f`, the
+/// values and their contents must be `Clone`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use] extern crate mdo;
+/// fn main() {
+///     use mdo::option::{bind, ret};
+///     assert_eq!(mdo_map!(|a, b| a + b; Some(1), Some(2)), Some(3));
+///     assert_eq!(mdo_map!(|a, b| a + b; Some(1), None::<i32>), None);
+/// }
+/// ```
+#[macro_export]
+macro_rules! mdo_map {
+    (
+        @eval $f: expr ; [ $( $n: ident )* ] $m: expr $( , $ms: expr )*
+    ) => (
+        { let m = $m ; mdo_map! { @eval $f ; [ $( $n )* m ] $( $ms ),* } }
+    );
 
-    #[test]
-    fn option_bind() {
-        use super::option::{bind, ret, mzero};
-        let x = ret(5);
-        assert_eq!(x, Some(5));
-        let x = bind(ret(5), |x| ret(x + 1));
-        assert_eq!(x, Some(6));
-        let x = bind(ret(5), |x| bind(ret(x + 5), |x| ret(x * 2)));
-        assert_eq!(x, Some(20));
-        let x = bind(ret(5i32), |x| bind(if x == 0 { ret(()) } else { mzero() },
-                                         |_| ret(x * 2)));
-        assert_eq!(x, None);
-    }
+    (
+        @eval $f: expr ; [ $( $n: ident )* ]
+    ) => (
+        { let f = $f ; mdo_map! { @lift f [] [ $( $n )* ] } }
+    );
 
-    #[test]
-    fn option_mdo() {
-        use super::option::{bind, ret, mzero};
-        let x = mdo! {
-            ret ret(5)
-        };
-        assert_eq!(x, Some(5));
-        let x = mdo! {
-            x =<< ret(5);
-            ret ret(x + 1)
-        };
-        assert_eq!(x, Some(6));
-        let x = mdo! {
-            x =<< ret(5);
-            x =<< ret(x + 5);
-            ret ret(x * 2)
-        };
-        assert_eq!(x, Some(20));
-        let x = mdo! {
-            x =<< ret(5i32);
-            when x == 0;
-            ret ret(x * 2)
-        };
-        assert_eq!(x, None);
-    }
+    (
+        @lift $f: ident [ $( $v: ident )* ] []
+    ) => (
+        ret($f($( $v.clone() ),*))
+    );
 
-    #[test]
-    fn let_type() {
-        let _: i32 = mdo! {
-            let i: i32 = 0;
-            ret i
-        };
-    }
+    (
+        @lift $f: ident [ $( $v: ident )* ] [ $m: ident $( $ms: ident )* ]
+    ) => (
+        bind($m, move |x| {
+            let $f = $f.clone();
+            $( let $v = $v.clone(); )*
+            $( let $ms = $ms.clone(); )*
+            mdo_map! { @lift $f [ $( $v )* x ] [ $( $ms )* ] }
+        })
+    );
 
-    #[test]
-    fn iter_bind() {
-        use super::iter::{bind, ret, mzero};
-        let l = bind(0..3, move |x| x..3);
-        assert_eq!(l.collect::<Vec<_>>(), vec![0, 1, 2, 1, 2, 2]);
-        let l = bind(0i32..3, move |x|
-                     bind(0..3, move |y| ret(x + y)));
-        assert_eq!(l.collect::<Vec<_>>(), vec![0, 1, 2, 1, 2, 3, 2, 3, 4]);
-        let l = bind(1i32..11, move |z|
-                     bind(1..z + 1, move |y|
-                          bind(1..y + 1, move |x|
-                               bind(if x * x + y * y == z * z { ret(()) }
-                                    else { mzero() },
-                                    move |_|
-                                    ret((x, y, z))))));
-        assert_eq!(l.collect::<Vec<_>>(), vec![(3, 4, 5), (6, 8, 10)]);
-    }
+    (
+        $f: expr ; $( $m: expr ),+
+    ) => (
+        mdo_map! { @eval $f ; [] $( $m ),+ }
+    )
+}
 
-    #[test]
+/// Inline bindings on `Option` or `Result`, without a full `mdo!` block
+///
+/// `mdo_let!(x <- a, y <- b; body)` is equivalent to
+/// `a.and_then(|x| b.map(|y| body))`: the last bound value is mapped
+/// with the pure `body` expression, so no `ret` is needed.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use] extern crate mdo;
+/// fn main() {
+///     let words = vec!["mdo", "let"];
+///     let len = mdo_let!(w <- words.first(), c <- w.chars().next(); (c, w.len()));
+///     assert_eq!(len, Some(('m', 3)));
+/// }
+/// ```
+#[macro_export]
+macro_rules! mdo_let {
+    (
+        $p: ident <- $e: expr ; $body: expr
+    ) => (
+        $e.map(|$p| $body)
+    );
+
+    (
+        $p: ident <- $e: expr , $( $t: tt )+
+    ) => (
+        $e.and_then(|$p| mdo_let! { $( $t )+ })
+    )
+}
+
+/// Monadic while loop using duck typing
+///
+/// `mdo_while!(x = init; cond; body)` starts with `x` bound to `init`,
+/// and while the monadic `cond` holds, binds `x` to the result of the
+/// monadic `body`. In `cond`, `x` is borrowed. A `while_m` function
+/// must be in scope, iterating without recursion:
+///
+/// * for `Option`, the loop continues on `Some(true)`, returns the
+///   state on `Some(false)` and returns `None` if `cond` or `body` is
+///   `None`;
+///
+/// * for iterators, each true value of `cond` continues with each
+///   value of `body`, each false value yielding the state.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use] extern crate mdo;
+/// fn main() {
+///     use mdo::option::{ret, while_m};
+///     let r = mdo_while!(x = 1; ret(*x < 100); ret(x * 3));
+///     assert_eq!(r, Some(243));
+/// }
+/// ```
+#[macro_export]
+macro_rules! mdo_while {
+    (
+        $p: ident = $init: expr ; $cond: expr ; $body: expr
+    ) => (
+        while_m($init, |$p| $cond, |$p| $body)
+    )
+}
+
+/// Monadic do notation for futures
+///
+/// Same syntax as `mdo!` without `when`, binds using
+/// `mdo::future::bind`, thus not needing any function in scope. The
+/// resulting future runs each bound future in sequence.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use] extern crate mdo;
+/// use std::future::Future;
+/// use std::task::{Context, Poll, Waker};
+///
+/// // a minimal executor, for futures that never wait
+/// fn block_on<F: Future>(f: F) -> F::Output {
+///     let mut f = Box::pin(f);
+///     let mut cx = Context::from_waker(Waker::noop());
+///     loop {
+///         if let Poll::Ready(x) = f.as_mut().poll(&mut cx) {
+///             return x;
+///         }
+///     }
+/// }
+///
+/// fn main() {
+///     use mdo::future::ret;
+///     let f = mdo_async! {
+///         x =<< ret(1);
+///         let y = x + 1;
+///         z =<< std::future::ready(y * 2);
+///         ret ret(z)
+///     };
+///     assert_eq!(block_on(f), 4);
+/// }
+/// ```
+#[macro_export]
+macro_rules! mdo_async {
+    (
+        let $p: pat = $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p = $e ; mdo_async! { $( $t )* } }
+    );
+
+    (
+        let $p: ident : $ty: ty = $e: expr ; $( $t: tt )*
+    ) => (
+        { let $p: $ty = $e ; mdo_async! { $( $t )* } }
+    );
+
+    (
+        $p: pat =<< $e: expr ; $( $t: tt )*
+    ) => (
+        $crate::future::bind($e, move |$p| mdo_async! { $( $t )* } )
+    );
+
+    (
+        $p: ident : $ty: ty =<< $e: expr ; $( $t: tt )*
+    ) => (
+        $crate::future::bind($e, move |$p : $ty| mdo_async! { $( $t )* } )
+    );
+
+    (
+        ign $e: expr ; $( $t: tt )*
+    ) => (
+        $crate::future::bind($e, move |_| mdo_async! { $( $t )* })
+    );
+
+    (
+        ret $f: expr
+    ) => (
+        $f
+    )
+}
+
+pub mod option {
+    //! Monadic functions for Option<T>
+
+    use std::option::IntoIter;
+
+    /// bind for Option<T>, equivalent to `m.and_then(f)`
+    pub fn bind<T, U, F: FnOnce(T) -> Option<U>>(m: Option<T>, f: F) -> Option<U> {
+        m.and_then(f)
+    }
+
+    /// return for Option<T>, equivalent to `Some(x)`
+    pub const fn ret<T>(x: T) -> Option<T> {
+        Some(x)
+    }
+
+    /// return for Option<&T>, equivalent to `Some(x)`; the same as
+    /// `ret`, naming the borrowing case to keep the chain on references
+    /// instead of moving out of borrowed data
+    pub const fn ret_ref<T: ?Sized>(x: &T) -> Option<&T> {
+        Some(x)
+    }
+
+    /// mzero for Option<T>, equivalent to `None`
+    pub const fn mzero<T>() -> Option<T> {
+        None
+    }
+
+    /// Monadic while loop for Option<T>: while `cond` is `Some(true)`,
+    /// the state is replaced by `body`. `None` from `cond` or `body`
+    /// gives `None`.
+    pub fn while_m<S, F, G>(init: S, cond: F, body: G) -> Option<S>
+    where F: FnMut(&S) -> Option<bool>, G: FnMut(S) -> Option<S> {
+        let (mut s, mut cond, mut body) = (init, cond, body);
+        while cond(&s)? {
+            s = body(s)?;
+        }
+        Some(s)
+    }
+
+    /// Alternative for Option<T>, the "try this, then that" pattern:
+    /// `f` is only called if `m` is `None`, equivalent to `m.or_else(f)`
+    pub fn or_else_m<T, F: FnOnce() -> Option<T>>(m: Option<T>, f: F) -> Option<T> {
+        m.or_else(f)
+    }
+
+    /// The first `Some` of `choices`, `None` if there is none
+    pub fn iter_opt<T, I: IntoIterator<Item = Option<T>>>(choices: I) -> Option<T> {
+        choices.into_iter().flatten().next()
+    }
+
+    /// All the values, `None` if any of them is `None`
+    pub fn all_some<T, I: IntoIterator<Item = Option<T>>>(xs: I) -> Option<Vec<T>> {
+        xs.into_iter().collect()
+    }
+
+    /// The first `Some` returned by the `choices` closures, called in
+    /// order until one succeeds
+    pub fn first_ok<T, F, I>(choices: I) -> Option<T>
+    where F: FnOnce() -> Option<T>, I: IntoIterator<Item = F> {
+        choices.into_iter().find_map(|f| f())
+    }
+
+    /// Swap an Option<Result<T, E>> into a Result<Option<T>, E>,
+    /// equivalent to `m.transpose()`
+    pub fn transpose<T, E>(m: Option<Result<T, E>>) -> Result<Option<T>, E> {
+        m.transpose()
+    }
+
+    /// Iterator of `Some(item)` for each item of the iterator if `o` is
+    /// `Some`, or a single `None` if `o` is `None`
+    pub fn transpose_to_iter<T, I>(o: Option<I>) -> impl Iterator<Item = Option<T>>
+    where I: Iterator<Item = T> {
+        let none = if o.is_none() { Some(None) } else { None };
+        o.into_iter().flatten().map(Some).chain(none)
+    }
+
+    /// Iterator of the `Some` values, skipping the `None` ones,
+    /// equivalent to `it.flatten()`
+    pub fn transpose_from_iter<T, I>(it: I) -> impl Iterator<Item = T>
+    where I: Iterator<Item = Option<T>> {
+        it.flatten()
+    }
+
+    /// `Some(x)` if `f(&x)` is true, `None` otherwise
+    pub fn from_predicate<T, F: FnOnce(&T) -> bool>(x: T, f: F) -> Option<T> {
+        if f(&x) { Some(x) } else { None }
+    }
+
+    /// Construct an Option<U> from `x` using `f`, equivalent to `f(x)`
+    pub fn from_predicate_with<T, U, F: FnOnce(T) -> Option<U>>(x: T, f: F) -> Option<U> {
+        f(x)
+    }
+
+    /// `Some(value)` if `cond` holds, `None` otherwise, equivalent to
+    /// `cond.then_some(value)`
+    pub fn some_if<T>(cond: bool, value: T) -> Option<T> {
+        cond.then_some(value)
+    }
+
+    /// `Some(f())` if `cond` holds, `None` otherwise, `f` being called
+    /// only if needed, equivalent to `cond.then(f)`
+    pub fn some_if_else<T, F: FnOnce() -> T>(cond: bool, f: F) -> Option<T> {
+        cond.then(f)
+    }
+
+    /// Sum of the values, `None` if any value is `None`. The sum of
+    /// no value is `Some(0)`.
+    pub fn try_sum<I: IntoIterator<Item = Option<i64>>>(i: I) -> Option<i64> {
+        i.into_iter().sum()
+    }
+
+    /// Product of the values, `None` if any value is `None`. The
+    /// product of no value is `Some(1)`.
+    pub fn try_product<I: IntoIterator<Item = Option<i64>>>(i: I) -> Option<i64> {
+        i.into_iter().product()
+    }
+
+    /// Number of `Some` values
+    pub fn count_some<T, I: IntoIterator<Item = Option<T>>>(i: I) -> usize {
+        i.into_iter().filter(Option::is_some).count()
+    }
+
+    /// join for Option<T>, equivalent to `m.flatten()`
+    pub fn flatten<T>(m: Option<Option<T>>) -> Option<T> {
+        m.flatten()
+    }
+
+    /// Remove two levels of Option
+    pub fn flatten_n<T>(m: Option<Option<Option<T>>>) -> Option<T> {
+        m.flatten().flatten()
+    }
+
+    /// Combine two options with `f`, `None` if any of them is `None`
+    pub fn zip_with<A, B, C, F: FnOnce(A, B) -> C>(a: Option<A>, b: Option<B>, f: F) -> Option<C> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(f(a, b)),
+            _ => None,
+        }
+    }
+
+    /// Sequence two options keeping the second, equivalent to `a.and(b)`
+    pub fn and_m<A, B>(a: Option<A>, b: Option<B>) -> Option<B> {
+        a.and(b)
+    }
+
+    /// Exclusive alternative, the value of the only `Some`, `None` if
+    /// both or neither are `Some`, equivalent to `a.xor(b)`
+    pub fn xor<T>(a: Option<T>, b: Option<T>) -> Option<T> {
+        a.xor(b)
+    }
+
+    /// Thread `init` through the fallible `fns` in order, stopping at
+    /// the first `None`
+    pub fn pipe<T>(init: Option<T>, fns: &[fn(T) -> Option<T>]) -> Option<T> {
+        fns.iter().fold(init, |m, f| m.and_then(f))
+    }
+
+    /// Pair two options, `None` if any of them is `None`
+    pub fn zip_options<A, B>(a: Option<A>, b: Option<B>) -> Option<(A, B)> {
+        zip_with(a, b, |a, b| (a, b))
+    }
+
+    /// Combine three options, `None` if any of them is `None`
+    pub fn zip3<A, B, C>(a: Option<A>, b: Option<B>, c: Option<C>) -> Option<(A, B, C)> {
+        Some((a?, b?, c?))
+    }
+
+    /// Combine four options, `None` if any of them is `None`
+    pub fn zip4<A, B, C, D>(a: Option<A>, b: Option<B>, c: Option<C>, d: Option<D>)
+                            -> Option<(A, B, C, D)> {
+        Some((a?, b?, c?, d?))
+    }
+
+    /// Combine five options, `None` if any of them is `None`
+    pub fn zip5<A, B, C, D, E>(a: Option<A>, b: Option<B>, c: Option<C>, d: Option<D>,
+                               e: Option<E>)
+                               -> Option<(A, B, C, D, E)> {
+        Some((a?, b?, c?, d?, e?))
+    }
+
+    /// Convert the option to a value with `some_fn` or `none_fn`,
+    /// equivalent to `o.map_or_else(none_fn, some_fn)`
+    pub fn bimap<T, U, F, G>(some_fn: F, none_fn: G, o: Option<T>) -> U
+    where F: FnOnce(T) -> U, G: FnOnce() -> U {
+        o.map_or_else(none_fn, some_fn)
+    }
+
+    /// Call `f` on the value, if any, and return `m` unchanged,
+    /// equivalent to `m.inspect(f)`
+    pub fn tap<T, F: FnOnce(&T)>(m: Option<T>, f: F) -> Option<T> {
+        m.inspect(f)
+    }
+
+    /// Lift an option into the iterator monad, yielding its value if
+    /// any, equivalent to `o.into_iter()`
+    pub fn lift_iter<T>(o: Option<T>) -> IntoIter<T> {
+        o.into_iter()
+    }
+
+    /// The value or `T::default()`, equivalent to `o.unwrap_or_default()`;
+    /// useful as a function argument, e.g. `.map(option::or_default)`
+    pub fn or_default<T: Default>(o: Option<T>) -> T {
+        o.unwrap_or_default()
+    }
+
+    /// The value or the result of `f`, equivalent to
+    /// `o.unwrap_or_else(f)`; prefer `or_default` when `T::default()`
+    /// is enough
+    pub fn or_else_default<T, F: FnOnce() -> T>(f: F, o: Option<T>) -> T {
+        o.unwrap_or_else(f)
+    }
+
+    /// Convert to a result, computing the error lazily with `f`,
+    /// equivalent to `o.ok_or_else(f)`; use it to leave an option
+    /// comprehension for a result one
+    pub fn to_result_with<T, E, F: FnOnce() -> E>(f: F, o: Option<T>) -> Result<T, E> {
+        o.ok_or_else(f)
+    }
+
+    /// Convert a result to an option, dropping the error, equivalent to
+    /// `res.ok()`
+    pub fn from_result<T, E>(res: Result<T, E>) -> Option<T> {
+        res.ok()
+    }
+
+    /// The value of `cell`, computing it with `f` and storing it if
+    /// `None`, so that `f` is only called once
+    pub fn cache<T: Clone, F: FnOnce() -> T>(cell: &mut Option<T>, f: F) -> T {
+        cell.get_or_insert_with(f).clone()
+    }
+
+    /// Run `f`, returning `None` if it panics.
+    ///
+    /// This is a last resort for calling code that may `unwrap` on
+    /// invalid input: the panic is still reported by the panic hook,
+    /// and panics aborting the process are not caught. Only available
+    /// with the `catching` feature.
+    #[cfg(feature = "catching")]
+    pub fn catching<T, F: FnOnce() -> T + ::std::panic::UnwindSafe>(f: F) -> Option<T> {
+        ::std::panic::catch_unwind(f).ok()
+    }
+}
+
+pub mod result {
+    //! Monadic functions for Result<T, E>
+
+    use std::error::Error;
+    use std::fmt::{self, Debug, Display};
+
+    /// bind for Result<T, E>, equivalent to `m.and_then(f)`
+    pub fn bind<T, E, U, F: FnOnce(T) -> Result<U, E>>(m: Result<T, E>, f: F) -> Result<U, E> {
+        m.and_then(f)
+    }
+
+    /// return for Result<T, E>, equivalent to `Ok(x)`
+    pub const fn ret<T, E>(x: T) -> Result<T, E> {
+        Ok(x)
+    }
+
+    /// guard for Result<T, E>, `Ok(())` if `cond` holds, `Err(f())`
+    /// otherwise
+    pub fn ensure<E, F: FnOnce() -> E>(cond: bool, f: F) -> Result<(), E> {
+        if cond { Ok(()) } else { Err(f()) }
+    }
+
+    /// Convert an option to a result, `None` giving `err`, equivalent to
+    /// `opt.ok_or(err)`
+    pub fn from_option<T, E>(opt: Option<T>, err: E) -> Result<T, E> {
+        opt.ok_or(err)
+    }
+
+    /// `Ok(value)` if `cond` holds, `Err(err)` otherwise
+    pub fn ok_if<T, E>(cond: bool, value: T, err: E) -> Result<T, E> {
+        if cond { Ok(value) } else { Err(err) }
+    }
+
+    /// bind for Result<T, E> converting the error with `err_fn`,
+    /// equivalent to `m.map_err(err_fn).and_then(k)`
+    pub fn map_err_bind<T, E, F, E2, U, G>(m: Result<T, E>, err_fn: F, k: G) -> Result<U, E2>
+    where F: FnOnce(E) -> E2, G: FnOnce(T) -> Result<U, E2> {
+        m.map_err(err_fn).and_then(k)
+    }
+
+    /// Split an iterator of results into its successful values and
+    /// its errors, without short-circuiting.
+    pub fn partition<T, E, I: IntoIterator<Item = Result<T, E>>>(i: I) -> (Vec<T>, Vec<E>) {
+        let mut oks = Vec::new();
+        let mut errs = Vec::new();
+        for r in i {
+            match r {
+                Ok(x) => oks.push(x),
+                Err(e) => errs.push(e),
+            }
+        }
+        (oks, errs)
+    }
+
+    /// Swap a Result<Option<T>, E> into an Option<Result<T, E>>,
+    /// equivalent to `m.transpose()`
+    pub fn transpose<T, E>(m: Result<Option<T>, E>) -> Option<Result<T, E>> {
+        m.transpose()
+    }
+
+    /// Collect the values of the results, or return the first error.
+    pub fn sequence<T, E, I: IntoIterator<Item = Result<T, E>>>(i: I) -> Result<Vec<T>, E> {
+        i.into_iter().collect()
+    }
+
+    /// Apply `f` to the values and collect the results, or return the
+    /// first error.
+    pub fn traverse<A, B, E, F, I>(f: F, xs: I) -> Result<Vec<B>, E>
+    where F: FnMut(A) -> Result<B, E>, I: IntoIterator<Item = A> {
+        sequence(xs.into_iter().map(f))
+    }
+
+    /// Collect the values of the results, or all the errors.
+    pub fn sequence_all_errors<T, E, I>(xs: I) -> Result<Vec<T>, Vec<E>>
+    where I: IntoIterator<Item = Result<T, E>> {
+        let (oks, errs) = partition(xs);
+        if errs.is_empty() { Ok(oks) } else { Err(errs) }
+    }
+
+    /// Apply `f` to the values and collect the results, or all the
+    /// errors.
+    pub fn traverse_all_errors<A, B, E, F, I>(f: F, xs: I) -> Result<Vec<B>, Vec<E>>
+    where F: FnMut(A) -> Result<B, E>, I: IntoIterator<Item = A> {
+        sequence_all_errors(xs.into_iter().map(f))
+    }
+
+    /// The first `Ok` value, stopping there, or all the errors if there
+    /// is none
+    pub fn first_ok<T, E, I: IntoIterator<Item = Result<T, E>>>(xs: I) -> Result<T, Vec<E>> {
+        let mut errs = Vec::new();
+        for x in xs {
+            match x {
+                Ok(x) => return Ok(x),
+                Err(e) => errs.push(e),
+            }
+        }
+        Err(errs)
+    }
+
+    /// Monadic fold over borrowed values, stopping at the first error,
+    /// equivalent to `iter.into_iter().try_fold(init, f)`
+    pub fn fold_ref<'a, A: 'a, B, E, I, F>(init: B, iter: I, f: F) -> Result<B, E>
+    where I: IntoIterator<Item = &'a A>, F: FnMut(B, &'a A) -> Result<B, E> {
+        iter.into_iter().try_fold(init, f)
+    }
+
+    /// Distribute a Result<Vec<T>, E> into a Vec<Result<T, E>>, an
+    /// error giving a vector containing only this error.
+    pub fn broadcast<T, E>(m: Result<Vec<T>, E>) -> Vec<Result<T, E>> {
+        match m {
+            Ok(v) => v.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        }
+    }
+
+    /// Call `f` on the error, if any, and return `m` unchanged,
+    /// equivalent to `m.inspect_err(f)`
+    pub fn tap_err<T, E, F: FnOnce(&E)>(m: Result<T, E>, f: F) -> Result<T, E> {
+        m.inspect_err(f)
+    }
+
+    /// Call `f` on the value, if any, and return `m` unchanged,
+    /// equivalent to `m.inspect(f)`
+    pub fn inspect<T, E, F: FnOnce(&T)>(m: Result<T, E>, f: F) -> Result<T, E> {
+        m.inspect(f)
+    }
+
+    /// Convert the error to a string prefixed by `context`, as
+    /// `"context: error"`.
+    pub fn annotate<T, E: Display>(context: &str, r: Result<T, E>) -> Result<T, String> {
+        r.map_err(|e| format!("{}: {}", context, e))
+    }
+
+    /// Same as `annotate`, the context being computed by `f` only on
+    /// error.
+    pub fn annotate_with<T, E, F>(f: F, r: Result<T, E>) -> Result<T, String>
+    where E: Display, F: FnOnce() -> String {
+        r.map_err(|e| format!("{}: {}", f(), e))
+    }
+
+    /// Prepend the context computed by `f` to the error, as
+    /// `"context: error"`, `f` being only called on error.
+    pub fn context<T, E, C, F>(m: Result<T, E>, f: F) -> Result<T, String>
+    where E: Display, C: Display, F: FnOnce() -> C {
+        annotate_with(|| f().to_string(), m)
+    }
+
+    /// An error with a context message, its `source()` being the
+    /// original error.
+    #[derive(Debug)]
+    pub struct ChainedError<C, E> {
+        /// The context message, displayed by the error.
+        pub context: C,
+        /// The original error.
+        pub cause: E,
+    }
+
+    impl<C: Display, E> Display for ChainedError<C, E> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.context.fmt(f)
+        }
+    }
+
+    impl<C: Display + Debug, E: Error + 'static> Error for ChainedError<C, E> {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.cause)
+        }
+    }
+
+    /// Wrap the error in a `ChainedError` with `context`, keeping the
+    /// original error as its `source()`
+    pub fn chain_err<T, E, C>(context: C, r: Result<T, E>) -> Result<T, ChainedError<C, E>>
+    where E: Error, C: Display {
+        r.map_err(|cause| ChainedError { context, cause })
+    }
+
+    /// join for Result<T, E>, equivalent to `m.and_then(|r| r)`
+    pub fn flatten<T, E>(m: Result<Result<T, E>, E>) -> Result<T, E> {
+        m.and_then(|r| r)
+    }
+
+    /// join for Result with different inner and outer error types,
+    /// both converted to `E`
+    pub fn flatten_nested<T, E1, E2, E>(m: Result<Result<T, E1>, E2>) -> Result<T, E>
+    where E: From<E1> + From<E2> {
+        match m {
+            Ok(Ok(x)) => Ok(x),
+            Ok(Err(e)) => Err(E::from(e)),
+            Err(e) => Err(E::from(e)),
+        }
+    }
+
+    /// Sequence two results keeping the second, equivalent to `a.and(b)`
+    pub fn and_then_discard<T, U, E>(a: Result<T, E>, b: Result<U, E>) -> Result<U, E> {
+        a.and(b)
+    }
+
+    /// Pair two results, the error of `a` taking precedence over the
+    /// error of `b`
+    pub fn zip<A, B, E>(a: Result<A, E>, b: Result<B, E>) -> Result<(A, B), E> {
+        a.and_then(|a| b.map(|b| (a, b)))
+    }
+
+    /// Combine three results, returning the first error
+    pub fn zip3<A, B, C, E>(a: Result<A, E>, b: Result<B, E>, c: Result<C, E>)
+                            -> Result<(A, B, C), E> {
+        Ok((a?, b?, c?))
+    }
+
+    /// Combine four results, returning the first error
+    pub fn zip4<A, B, C, D, E>(a: Result<A, E>, b: Result<B, E>, c: Result<C, E>, d: Result<D, E>)
+                               -> Result<(A, B, C, D), E> {
+        Ok((a?, b?, c?, d?))
+    }
+
+    /// Combine five results, returning the first error
+    pub fn zip5<A, B, C, D, F, E>(a: Result<A, E>, b: Result<B, E>, c: Result<C, E>,
+                                  d: Result<D, E>, e: Result<F, E>)
+                                  -> Result<(A, B, C, D, F), E> {
+        Ok((a?, b?, c?, d?, e?))
+    }
+
+    /// Pair two results, collecting all the errors
+    pub fn zip_all<A, B, E>(a: Result<A, E>, b: Result<B, E>) -> Result<(A, B), Vec<E>> {
+        match (a, b) {
+            (Ok(a), Ok(b)) => Ok((a, b)),
+            (a, b) => Err(a.err().into_iter().chain(b.err()).collect()),
+        }
+    }
+
+    /// Combine three results, collecting all the errors
+    pub fn zip_all3<A, B, C, E>(a: Result<A, E>, b: Result<B, E>, c: Result<C, E>)
+                                -> Result<(A, B, C), Vec<E>> {
+        match (a, b, c) {
+            (Ok(a), Ok(b), Ok(c)) => Ok((a, b, c)),
+            (a, b, c) => Err(a.err().into_iter().chain(b.err()).chain(c.err()).collect()),
+        }
+    }
+
+    /// Combine four results, collecting all the errors
+    pub fn zip_all4<A, B, C, D, E>(a: Result<A, E>, b: Result<B, E>, c: Result<C, E>,
+                                   d: Result<D, E>)
+                                   -> Result<(A, B, C, D), Vec<E>> {
+        match (a, b, c, d) {
+            (Ok(a), Ok(b), Ok(c), Ok(d)) => Ok((a, b, c, d)),
+            (a, b, c, d) => Err(a.err().into_iter()
+                                .chain(b.err()).chain(c.err()).chain(d.err())
+                                .collect()),
+        }
+    }
+
+    /// Combine five results, collecting all the errors
+    pub fn zip_all5<A, B, C, D, F, E>(a: Result<A, E>, b: Result<B, E>, c: Result<C, E>,
+                                      d: Result<D, E>, e: Result<F, E>)
+                                      -> Result<(A, B, C, D, F), Vec<E>> {
+        match (a, b, c, d, e) {
+            (Ok(a), Ok(b), Ok(c), Ok(d), Ok(e)) => Ok((a, b, c, d, e)),
+            (a, b, c, d, e) => Err(a.err().into_iter()
+                                   .chain(b.err()).chain(c.err()).chain(d.err()).chain(e.err())
+                                   .collect()),
+        }
+    }
+
+    /// The first result if it is `Ok`, the second otherwise, equivalent
+    /// to `a.or(b)`
+    pub fn or<T, E, F>(a: Result<T, E>, b: Result<T, F>) -> Result<T, F> {
+        a.or(b)
+    }
+
+    /// Recover from the error with `f`, possibly with a new error type,
+    /// `f` being only called on error, equivalent to `m.or_else(f)`
+    pub fn or_else_m<T, E, E2, F>(m: Result<T, E>, f: F) -> Result<T, E2>
+    where F: FnOnce(E) -> Result<T, E2> {
+        m.or_else(f)
+    }
+
+    /// Drop the error after printing it to `stderr`, unlike `r.ok()`
+    /// which discards it silently; meant for best-effort steps where a
+    /// failure should be reported but not propagated
+    pub fn ok_or_log<T, E: Debug>(r: Result<T, E>) -> Option<T> {
+        r.map_err(|e| eprintln!("{:?}", e)).ok()
+    }
+
+    /// Map the value or the error with `f`, for results having the
+    /// same type on both sides
+    pub fn map_both<T, U, F: FnOnce(T) -> U>(f: F, r: Result<T, T>) -> Result<U, U> {
+        match r {
+            Ok(x) => Ok(f(x)),
+            Err(e) => Err(f(e)),
+        }
+    }
+
+    /// Map the value with `ok_fn` and the error with `err_fn`
+    pub fn bimap<T, U, E, F, G, H>(ok_fn: G, err_fn: H, r: Result<T, E>) -> Result<U, F>
+    where G: FnOnce(T) -> U, H: FnOnce(E) -> F {
+        match r {
+            Ok(x) => Ok(ok_fn(x)),
+            Err(e) => Err(err_fn(e)),
+        }
+    }
+}
+
+pub mod iter {
+    //! Monadic functions for Iterator<T>
+
+    use std::option;
+    use std::collections::{HashMap, VecDeque};
+    use std::fmt::{self, Debug};
+    use std::hash::Hash;
+    use std::iter::{self, FlatMap, Peekable, Repeat, Rev, StepBy, Take};
+    use std::ops::RangeFrom;
+
+    /// bind for Iterator<T, E>, equivalent to
+    /// `m.into_iter().flat_map(f)`
+    ///
+    /// `m` and the values returned by `f` can be anything implementing
+    /// `IntoIterator`, as `&Vec<T>` or `&[T]` iterating on `&T` without
+    /// consuming the collection. As `mdo!` closures are `move`, use a
+    /// reference binding (`let v = &v;`) to bind on it in nested steps.
+    ///
+    /// The result is a `DoubleEndedIterator` if `m` and the iterators
+    /// returned by `f` are, but never an `ExactSizeIterator` as its
+    /// length is only known after calling `f` on every value. See
+    /// `size_hint_bind` for that.
+    pub fn bind<I, U, F>(m: I, f: F) -> FlatMap<I::IntoIter, U, F>
+    where I: IntoIterator, U: IntoIterator, F: FnMut(I::Item) -> U {
+        m.into_iter().flat_map(f)
+    }
+
+    /// Same as `bind`, named after `Iterator::flat_map`: `x =<< m` in
+    /// `mdo!` is `flat_map_m(m, |x| ...)`, that is `m.flat_map(|x| ...)`.
+    pub fn flat_map_m<I, U, F>(m: I, f: F) -> FlatMap<I, U, F>
+    where I: Iterator, U: Iterator, F: FnMut(I::Item) -> U {
+        bind(m, f)
+    }
+
+    /// bind stopping the whole iteration as soon as `f` returns `None`,
+    /// equivalent to `outer.map_while(f).flatten()`
+    pub fn bind_while<I, U, F>(outer: I, f: F) -> impl Iterator<Item = U::Item>
+    where I: Iterator, U: IntoIterator, F: FnMut(I::Item) -> Option<U> {
+        outer.map_while(f).flatten()
+    }
+
+    /// bind passing the index of the value to `f`, equivalent to
+    /// `bind(outer.enumerate(), |(i, x)| f(i, x))`
+    pub fn bind_indexed<I, U, F>(outer: I, f: F) -> impl Iterator<Item = U::Item>
+    where I: Iterator, U: IntoIterator, F: FnMut(usize, I::Item) -> U {
+        let mut f = f;
+        bind(outer.enumerate(), move |(i, x)| f(i, x))
+    }
+
+    /// A boxed iterator, as returned by the function given to
+    /// `bind_boxed`.
+    pub type BoxedIter<'a, T> = Box<dyn Iterator<Item = T> + 'a>;
+
+    /// bind with boxed inner iterators, so that the branches of `f` can
+    /// return iterators of different types.
+    pub fn bind_boxed<'a, I, T, F>(outer: I, f: F) -> FlatMap<I::IntoIter, BoxedIter<'a, T>, F>
+    where I: IntoIterator, F: FnMut(I::Item) -> BoxedIter<'a, T> {
+        bind(outer, f)
+    }
+
+    /// join for Iterator<T>, equivalent to `it.flatten()`.
+    pub fn concat_m<I, J, T>(it: I) -> impl Iterator<Item = T>
+    where I: Iterator<Item = J>, J: IntoIterator<Item = T> {
+        it.flatten()
+    }
+
+    /// join for Vec<T>, allocating the result once.
+    pub fn concat_vec_m<T>(xs: Vec<Vec<T>>) -> Vec<T> {
+        let mut v = Vec::with_capacity(xs.iter().map(Vec::len).sum());
+        for x in xs {
+            v.extend(x);
+        }
+        v
+    }
+
+    /// bind on the next value of `m` without consuming it: `f` is
+    /// called on the peeked value, and the value is consumed only if
+    /// the result of `f` is not empty. Successive calls make a lookahead
+    /// parser, the rejected value staying in `m`.
+    pub fn peek_bind<I, U, F>(f: F, m: &mut Peekable<I>) -> impl Iterator<Item = U::Item>
+    where I: Iterator, U: IntoIterator, F: FnOnce(&I::Item) -> U {
+        let mut u = m.peek().map(f).into_iter().flatten().peekable();
+        if u.peek().is_some() {
+            m.next();
+        }
+        u
+    }
+
+    /// bind iterating from the end, equivalent to `bind(m, f).rev()`
+    pub fn rev_bind<I, U, F>(m: I, f: F) -> Rev<FlatMap<I, U, F>>
+    where I: DoubleEndedIterator, U: DoubleEndedIterator, F: FnMut(I::Item) -> U {
+        bind(m, f).rev()
+    }
+
+    /// bind calling `f` eagerly on every value of `m`, the result
+    /// having an exact `size_hint` when the iterators returned by `f`
+    /// have one. It is then an `ExactSizeIterator`.
+    pub fn size_hint_bind<I, U, F>(m: I, f: F) -> SizeHintBind<U>
+    where I: Iterator, U: Iterator, F: FnMut(I::Item) -> U {
+        SizeHintBind { iters: m.map(f).collect() }
+    }
+
+    /// Iterator returned by `size_hint_bind`.
+    pub struct SizeHintBind<U> {
+        iters: VecDeque<U>,
+    }
+
+    impl<U: Iterator> Iterator for SizeHintBind<U> {
+        type Item = U::Item;
+
+        fn next(&mut self) -> Option<U::Item> {
+            loop {
+                let x = self.iters.front_mut()?.next();
+                if x.is_some() {
+                    return x;
+                }
+                self.iters.pop_front();
+            }
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.iters.iter().map(Iterator::size_hint).fold((0, Some(0)), |(l, u), (l2, u2)| {
+                (l.saturating_add(l2), u.and_then(|u| u2.and_then(|u2| u.checked_add(u2))))
+            })
+        }
+    }
+
+    impl<U: DoubleEndedIterator> DoubleEndedIterator for SizeHintBind<U> {
+        fn next_back(&mut self) -> Option<U::Item> {
+            loop {
+                let x = self.iters.back_mut()?.next_back();
+                if x.is_some() {
+                    return x;
+                }
+                self.iters.pop_back();
+            }
+        }
+    }
+
+    impl<U: ExactSizeIterator> ExactSizeIterator for SizeHintBind<U> {}
+
+    /// return for Iterator<T>, an iterator with one value.
+    pub fn ret<T>(x: T) -> option::IntoIter<T> {
+        Some(x).into_iter()
+    }
+
+    /// mzero for Iterator<T>, an empty iterator.
+    pub fn mzero<T>() -> option::IntoIter<T> {
+        None.into_iter()
+    }
+
+    enum Step<S> {
+        Test(S),
+        Done(S),
+    }
+
+    /// Monadic while loop for Iterator<T>: every true value of `cond`
+    /// continues with every value of `body`, every false value yielding
+    /// the state. The states are explored depth first using an explicit
+    /// stack.
+    pub fn while_m<S, C, B, F, G>(init: S, cond: F, body: G) -> impl Iterator<Item = S>
+    where S: Clone,
+          C: IntoIterator<Item = bool>,
+          B: IntoIterator<Item = S>,
+          F: FnMut(&S) -> C,
+          G: FnMut(S) -> B {
+        let (mut cond, mut body) = (cond, body);
+        let mut stack = vec![Step::Test(init)];
+        iter::from_fn(move || loop {
+            match stack.pop()? {
+                Step::Done(s) => return Some(s),
+                Step::Test(s) => {
+                    let mut next = vec![];
+                    for b in cond(&s) {
+                        if b {
+                            next.extend(body(s.clone()).into_iter().map(Step::Test));
+                        } else {
+                            next.push(Step::Done(s.clone()));
+                        }
+                    }
+                    stack.extend(next.into_iter().rev());
+                }
+            }
+        })
+    }
+
+    /// Lift an option in an iterator comprehension, yielding its value
+    /// if any, equivalent to `o.into_iter()`.
+    pub fn lift_option<T>(o: Option<T>) -> option::IntoIter<T> {
+        o.into_iter()
+    }
+
+    /// The value of `opt`, or `default` if `None`, as an iterator with
+    /// exactly one value, unlike `lift_option`.
+    pub fn from_option_or<T>(opt: Option<T>, default: T) -> option::IntoIter<T> {
+        ret(opt.unwrap_or(default))
+    }
+
+    /// bind over the Cartesian product of two iterators, equivalent to
+    /// `bind(xs, |x| bind(ys.clone(), |y| f(x, y)))`
+    pub fn bind2<I, J, U, F>(xs: I, ys: J, f: F) -> impl Iterator<Item = U::Item>
+    where I: Iterator, I::Item: Clone, J: Iterator + Clone, U: Iterator,
+          F: Fn(I::Item, J::Item) -> U + Clone {
+        bind(xs, move |x| {
+            let f = f.clone();
+            bind(ys.clone(), move |y| f(x.clone(), y))
+        })
+    }
+
+    /// bind over the Cartesian product of three iterators, equivalent
+    /// to `bind(xs, |x| bind(ys.clone(), |y| bind(zs.clone(), |z| f(x, y, z))))`
+    pub fn bind3<I, J, K, U, F>(xs: I, ys: J, zs: K, f: F) -> impl Iterator<Item = U::Item>
+    where I: Iterator, I::Item: Clone, J: Iterator + Clone, J::Item: Clone,
+          K: Iterator + Clone, U: Iterator, F: Fn(I::Item, J::Item, K::Item) -> U + Clone {
+        bind(xs, move |x| {
+            let f = f.clone();
+            bind2(ys.clone(), zs.clone(), move |y, z| f(x.clone(), y, z))
+        })
+    }
+
+    /// Overlapping windows of size `n` over an iterator, as vectors.
+    ///
+    /// An iterator with less than `n` elements yields no window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn windows<I>(i: I, n: usize) -> impl Iterator<Item = Vec<I::Item>>
+    where I: Iterator, I::Item: Clone {
+        assert!(n != 0, "window size must be non-zero");
+        let mut i = i.fuse();
+        let mut window = VecDeque::with_capacity(n);
+        iter::from_fn(move || {
+            if window.len() == n {
+                window.pop_front();
+            }
+            while window.len() < n {
+                window.push_back(i.next()?);
+            }
+            Some(window.iter().cloned().collect())
+        })
+    }
+
+    /// Non-overlapping chunks of size `n` over an iterator, as vectors,
+    /// the last chunk containing the remaining elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn chunks<I: Iterator>(i: I, n: usize) -> impl Iterator<Item = Vec<I::Item>> {
+        assert!(n != 0, "chunk size must be non-zero");
+        let mut i = i;
+        iter::from_fn(move || {
+            let chunk = i.by_ref().take(n).collect::<Vec<_>>();
+            if chunk.is_empty() { None } else { Some(chunk) }
+        })
+    }
+
+    /// Overlapping pairs of consecutive elements of an iterator, the
+    /// windows of size 2 as tuples.
+    pub fn pairwise<I>(i: I) -> impl Iterator<Item = (I::Item, I::Item)>
+    where I: Iterator, I::Item: Clone {
+        let mut i = i;
+        let mut prev = i.next();
+        iter::from_fn(move || {
+            let next = i.next()?;
+            Some((prev.replace(next.clone())?, next))
+        })
+    }
+
+    /// Pass the values through, calling `f` with a
+    /// `"label[index]: value"` line for each of them, only formatted if
+    /// `f` uses it.
+    pub(crate) fn trace_enumerate_m<I, F>(label: &'static str, it: I, f: F)
+                                          -> impl Iterator<Item = I::Item>
+    where I: Iterator, I::Item: Debug, F: FnMut(fmt::Arguments) {
+        let mut f = f;
+        it.enumerate().map(move |(i, x)| {
+            f(format_args!("{}[{}]: {:?}", label, i, x));
+            x
+        })
+    }
+
+    /// Pass the values through, printing `"label[index]: value"` on
+    /// `stderr` for each of them, to trace the values surviving each
+    /// step of a pipeline. Nothing is printed in release builds.
+    pub fn debug_enumerate_m<I>(label: &'static str, it: I) -> impl Iterator<Item = I::Item>
+    where I: Iterator, I::Item: Debug {
+        trace_enumerate_m(label, it, |line| if cfg!(debug_assertions) { eprintln!("{}", line) })
+    }
+
+    /// Cycle an iterator, taking `n` items. An empty iterator yields
+    /// nothing.
+    pub fn cycle_take<I: Iterator + Clone>(i: I, n: usize) -> impl Iterator<Item = I::Item> {
+        i.cycle().take(n)
+    }
+
+    /// `x` repeated `n` times, equivalent to `repeat(x).take(n)`, as
+    /// `std::iter::repeat_n` needs Rust 1.82. `ign repeat_n((), k)`
+    /// duplicates each value `k` times.
+    #[allow(clippy::manual_repeat_n)]
+    pub fn repeat_n<T: Clone>(x: T, n: usize) -> Take<Repeat<T>> {
+        iter::repeat(x).take(n)
+    }
+
+    /// Cycle an iterator under control of `f`: before the cycle number
+    /// `n` (from 0), `f(n, it.clone())` gives the iterator to run, or
+    /// `None` to stop. The iteration also stops after a cycle yielding
+    /// nothing, so an empty iterator never hangs; otherwise it never
+    /// ends if `f` never returns `None`.
+    pub fn cycle_m<I, F>(it: I, f: F) -> impl Iterator<Item = I::Item>
+    where I: Iterator + Clone, F: Fn(usize, I) -> Option<I> {
+        let mut n = 0;
+        let mut cur = None;
+        let mut empty_cycle = false;
+        iter::from_fn(move || loop {
+            if let Some(x) = cur.as_mut().and_then(Iterator::next) {
+                empty_cycle = false;
+                return Some(x);
+            }
+            if empty_cycle {
+                return None;
+            }
+            cur = Some(f(n, it.clone())?);
+            empty_cycle = true;
+            n += 1;
+        }).fuse()
+    }
+
+    /// Run the iterator `n` times.
+    pub fn take_cycles<I: Iterator + Clone>(n: usize, it: I) -> impl Iterator<Item = I::Item> {
+        cycle_m(it, move |i, it| if i < n { Some(it) } else { None })
+    }
+
+    /// Every `step`-th element of `range`, starting with the first,
+    /// equivalent to `range.step_by(step)`. Panics if `step` is 0.
+    pub fn stepped<I: Iterator>(range: I, step: usize) -> StepBy<I> {
+        range.step_by(step)
+    }
+
+    /// Unbounded counter starting at `start`, equivalent to `start..`.
+    /// Bound it with `take`, `take_while_m` or a `when` guard.
+    pub fn count_from<T>(start: T) -> RangeFrom<T>
+    where RangeFrom<T>: Iterator<Item = T> {
+        start..
+    }
+
+    /// Iterator calling `f` until it returns `None`, equivalent to
+    /// `std::iter::from_fn(f)`.
+    pub fn from_fn_m<A, F: FnMut() -> Option<A>>(f: F) -> impl Iterator<Item = A> {
+        iter::from_fn(f)
+    }
+
+    /// Monadic unfold, the iterator yielding the values produced by
+    /// `f` from the state `init`, until it returns `None`.
+    pub fn unfold_m<S, A, F: FnMut(S) -> Option<(A, S)>>(init: S, f: F) -> impl Iterator<Item = A> {
+        let mut f = f;
+        let mut state = Some(init);
+        iter::from_fn(move || {
+            let (a, s) = f(state.take()?)?;
+            state = Some(s);
+            Some(a)
+        })
+    }
+
+    /// Lazy monadic running fold, yielding `init` and then each state
+    /// computed by `f`, until `f` returns `None` or `it` ends.
+    pub fn scanl_m<S, A, F, I>(f: F, init: S, it: I) -> impl Iterator<Item = S>
+    where S: Clone, F: Fn(S, A) -> Option<S>, I: Iterator<Item = A> {
+        let mut it = it;
+        let mut init = Some(init);
+        let mut last = None;
+        iter::from_fn(move || {
+            let s = match init.take() {
+                Some(s) => s,
+                None => f(last.take()?, it.next()?)?,
+            };
+            last = Some(s.clone());
+            Some(s)
+        })
+    }
+
+    /// Map threading a mutable state through the values, as
+    /// `Iterator::scan` without early termination.
+    pub fn scan_m<S, T, U, I, F>(i: I, init: S, f: F) -> impl Iterator<Item = U>
+    where I: Iterator<Item = T>, F: FnMut(&mut S, T) -> U {
+        let mut f = f;
+        i.scan(init, move |s, x| Some(f(s, x)))
+    }
+
+    /// Zip two iterators, equivalent to `a.zip(b)`.
+    pub fn zip_m<I: Iterator, J: Iterator>(a: I, b: J) -> impl Iterator<Item = (I::Item, J::Item)> {
+        a.zip(b)
+    }
+
+    /// Split an iterator of pairs, equivalent to `i.unzip()`.
+    pub fn unzip<A, B, I: Iterator<Item = (A, B)>>(i: I) -> (Vec<A>, Vec<B>) {
+        i.unzip()
+    }
+
+    /// Map with a fallible `f`, collecting the values or stopping at
+    /// the first error, same as `result::traverse(f, i)`.
+    pub fn try_map_collect<A, T, E, I, F>(i: I, f: F) -> Result<Vec<T>, E>
+    where I: IntoIterator<Item = A>, F: FnMut(A) -> Result<T, E> {
+        super::result::traverse(f, i)
+    }
+
+    /// Zip two iterators with `f`, stopping at the end of the shortest.
+    pub fn zip_with_m<A, B, C, F, I, J>(f: F, a: I, b: J) -> impl Iterator<Item = C>
+    where F: FnMut(A, B) -> C, I: Iterator<Item = A>, J: Iterator<Item = B> {
+        let mut f = f;
+        a.zip(b).map(move |(x, y)| f(x, y))
+    }
+
+    /// Remove consecutive duplicates of an iterator.
+    pub fn dedup<I>(i: I) -> impl Iterator<Item = I::Item>
+    where I: Iterator, I::Item: PartialEq {
+        let mut i = i.peekable();
+        iter::from_fn(move || {
+            let x = i.next()?;
+            while i.next_if_eq(&x).is_some() {}
+            Some(x)
+        })
+    }
+
+    /// Remove consecutive elements of `it` having the same key
+    /// computed by `f`, keeping the first one.
+    pub fn dedup_by_key_m<I, K, F>(f: F, it: I) -> impl Iterator<Item = I::Item>
+    where I: Iterator, K: PartialEq, F: Fn(&I::Item) -> K {
+        let mut it = it.peekable();
+        iter::from_fn(move || {
+            let x = it.next()?;
+            let k = f(&x);
+            while it.next_if(|y| f(y) == k).is_some() {}
+            Some(x)
+        })
+    }
+
+    /// Take the elements of `it` while `f` returns `Some(true)`,
+    /// stopping at `Some(false)` or `None`.
+    pub fn take_while_m<I, F>(f: F, it: I) -> impl Iterator<Item = I::Item>
+    where I: Iterator, F: Fn(&I::Item) -> Option<bool> {
+        it.map_while(move |x| if f(&x)? { Some(x) } else { None }).fuse()
+    }
+
+    /// Lazily map with the fallible `f`, stopping at the first `None`,
+    /// equivalent to `i.map_while(f).fuse()`.
+    pub fn take_while_some<A, T, I, F>(i: I, f: F) -> impl Iterator<Item = T>
+    where I: Iterator<Item = A>, F: FnMut(A) -> Option<T> {
+        i.map_while(f).fuse()
+    }
+
+    /// Skip the elements of `it` while `f` returns `Some(true)`,
+    /// yielding the rest from the first `Some(false)`. Stops if `f`
+    /// returns `None`.
+    pub fn drop_while_m<I, F>(f: F, it: I) -> impl Iterator<Item = I::Item>
+    where I: Iterator, F: Fn(&I::Item) -> Option<bool> {
+        let mut it = Some(it);
+        let mut dropping = true;
+        iter::from_fn(move || {
+            while dropping {
+                let x = it.as_mut()?.next()?;
+                match f(&x) {
+                    Some(true) => (),
+                    Some(false) => {
+                        dropping = false;
+                        return Some(x);
+                    }
+                    None => {
+                        it = None;
+                        return None;
+                    }
+                }
+            }
+            it.as_mut()?.next()
+        })
+    }
+
+    /// A prefix and the rest of an iterator, as returned by `span_m`.
+    type Span<T> = (Vec<T>, Vec<T>);
+
+    /// Split `it` into its longest prefix whose elements satisfy `f`,
+    /// and the rest.
+    pub fn span_m<I, F>(f: F, it: I) -> Span<I::Item>
+    where I: Iterator, F: Fn(&I::Item) -> bool {
+        span_option_m(|x| Some(f(x)), it).unwrap()
+    }
+
+    /// Same as `span_m` with a fallible predicate, `None` if `f`
+    /// returns `None` before the end of the prefix.
+    pub fn span_option_m<I, F>(f: F, it: I) -> Option<Span<I::Item>>
+    where I: Iterator, F: Fn(&I::Item) -> Option<bool> {
+        let mut it = it;
+        let mut prefix = vec![];
+        for x in it.by_ref() {
+            if f(&x)? {
+                prefix.push(x);
+            } else {
+                let mut suffix = vec![x];
+                suffix.extend(it);
+                return Some((prefix, suffix));
+            }
+        }
+        Some((prefix, vec![]))
+    }
+
+    /// Alternate the elements of two iterators, continuing with the
+    /// other one when one ends.
+    pub fn interleave<I, J>(a: I, b: J) -> impl Iterator<Item = I::Item>
+    where I: Iterator, J: Iterator<Item = I::Item> {
+        let mut a = a.fuse();
+        let mut b = b.fuse();
+        let mut from_a = false;
+        iter::from_fn(move || {
+            from_a = !from_a;
+            if from_a { a.next().or_else(|| b.next()) } else { b.next().or_else(|| a.next()) }
+        })
+    }
+
+    /// Insert `sep` between consecutive elements of `i`.
+    pub fn intersperse<T: Clone, I: Iterator<Item = T>>(i: I, sep: T) -> impl Iterator<Item = T> {
+        intersperse_with_m(move || sep.clone(), i)
+    }
+
+    /// Insert a separator computed by `f` between consecutive elements
+    /// of `it`, `f` being only called when a separator is needed.
+    pub fn intersperse_with_m<T, I, F>(f: F, it: I) -> impl Iterator<Item = T>
+    where I: Iterator<Item = T>, F: FnMut() -> T {
+        let mut f = f;
+        let mut it = it.peekable();
+        let mut sep_next = false;
+        iter::from_fn(move || {
+            if sep_next && it.peek().is_some() {
+                sep_next = false;
+                return Some(f());
+            }
+            sep_next = true;
+            it.next()
+        })
+    }
+
+    /// Alternative for Iterator<T>, the "try this, then that" pattern:
+    /// the elements of `primary` followed by the ones of `fallback`,
+    /// equivalent to `primary.chain(fallback)`.
+    pub fn alt_m<I: Iterator>(primary: I, fallback: I) -> impl Iterator<Item = I::Item> {
+        primary.chain(fallback)
+    }
+
+    /// Group the elements of `it` by the key computed by `f`, keeping
+    /// their order in each group. `it` is consumed immediately, and
+    /// the groups are yielded in an unspecified order.
+    pub fn group_by_m<I, K, F>(f: F, it: I) -> impl Iterator<Item = (K, Vec<I::Item>)>
+    where I: Iterator, K: Eq + Hash, F: Fn(&I::Item) -> K {
+        let mut groups = HashMap::new();
+        for x in it {
+            groups.entry(f(&x)).or_insert_with(Vec::new).push(x);
+        }
+        groups.into_iter()
+    }
+
+    /// Apply `f` to each overlapping window of `size` elements of
+    /// `xs`, skipping the windows for which `f` returns `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn windows_m<'a, T, U, F>(size: usize, f: F, xs: &'a [T]) -> impl Iterator<Item = U> + 'a
+    where F: Fn(&[T]) -> Option<U> + 'a {
+        xs.windows(size).filter_map(f)
+    }
+
+    /// Apply `f` to each non-overlapping chunk of `size` elements of
+    /// `xs`, the last one being possibly shorter, skipping the chunks
+    /// for which `f` returns `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn chunks_m<T, U, F>(size: usize, f: F, xs: Vec<T>) -> impl Iterator<Item = U>
+    where F: Fn(&[T]) -> Option<U> {
+        assert!(size != 0, "chunk size must be non-zero");
+        (0..xs.len()).step_by(size)
+            .filter_map(move |i| f(&xs[i..usize::min(i + size, xs.len())]))
+    }
+
+    /// bind on consecutive chunks of `chunk_size` values of `it`, the
+    /// last chunk containing the remaining values, as batching lookups.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn chunked_bind<I, U, F>(chunk_size: usize, f: F, it: I) -> impl Iterator<Item = U::Item>
+    where I: Iterator, U: IntoIterator, F: FnMut(Vec<I::Item>) -> U {
+        bind(chunks(it, chunk_size), f)
+    }
+
+    /// Cartesian product of `lists`, in lexicographic order. The
+    /// product of no list is a single empty vector.
+    pub fn product_m<'a, T: Clone + 'a>(lists: Vec<Vec<T>>) -> impl Iterator<Item = Vec<T>> + 'a {
+        let init: BoxedIter<'a, Vec<T>> = Box::new(ret(vec![]));
+        lists.into_iter().fold(init, |acc, list| {
+            Box::new(bind(acc, move |prefix: Vec<T>| {
+                list.clone().into_iter().map(move |x| {
+                    let mut v = prefix.clone();
+                    v.push(x);
+                    v
+                })
+            }))
+        })
+    }
+}
+
+pub mod lazy_option {
+    //! Monadic functions for LazyOption<T>, an Option<T> computed on demand
+
+    use std::cell::{Cell, OnceCell};
+
+    type Thunk<T> = Box<dyn FnOnce() -> Option<T>>;
+
+    /// An `Option<T>` whose computation is deferred until forced.
+    ///
+    /// The computation runs at most once, its result being cached.
+    pub struct LazyOption<T> {
+        value: OnceCell<Option<T>>,
+        thunk: Cell<Option<Thunk<T>>>,
+    }
+
+    impl<T> LazyOption<T> {
+        fn run(self) -> Option<T> {
+            match self.value.into_inner() {
+                Some(x) => x,
+                None => self.thunk.into_inner().expect("LazyOption already forced")(),
+            }
+        }
+    }
+
+    /// Create a LazyOption<T> computed by `f` when forced.
+    pub fn lazy_option<T, F: FnOnce() -> Option<T> + 'static>(f: F) -> LazyOption<T> {
+        LazyOption { value: OnceCell::new(), thunk: Cell::new(Some(Box::new(f))) }
+    }
+
+    /// bind for LazyOption<T>, neither `m` nor `f` is evaluated until
+    /// the result is forced.
+    pub fn bind<T: 'static, U, F>(m: LazyOption<T>, f: F) -> LazyOption<U>
+    where F: FnOnce(T) -> LazyOption<U> + 'static {
+        lazy_option(move || m.run().and_then(|x| f(x).run()))
+    }
+
+    /// return for LazyOption<T>, an already computed `Some(x)`.
+    pub fn ret<T>(x: T) -> LazyOption<T> {
+        LazyOption { value: OnceCell::from(Some(x)), thunk: Cell::new(None) }
+    }
+
+    /// mzero for LazyOption<T>, an already computed `None`.
+    pub fn mzero<T>() -> LazyOption<T> {
+        LazyOption { value: OnceCell::from(None), thunk: Cell::new(None) }
+    }
+
+    /// mplus for LazyOption<T>, `n` is only evaluated if `m` is `None`.
+    pub fn mplus<T: 'static>(m: LazyOption<T>, n: LazyOption<T>) -> LazyOption<T> {
+        lazy_option(move || m.run().or_else(|| n.run()))
+    }
+
+    /// Force the computation, returning a reference to its result.
+    pub fn force<T>(m: &LazyOption<T>) -> Option<&T> {
+        m.value.get_or_init(|| m.thunk.take().expect("LazyOption already forced")()).as_ref()
+    }
+}
+
+pub mod monad {
+    //! A Monad trait, used by `mdo_m!`
+
+    /// A monad wrapping values of type `Item`.
+    pub trait Monad {
+        /// Type of the wrapped values.
+        type Item;
+
+        /// The same monad wrapping values of type `U`.
+        type Output<U>;
+
+        /// bind, sequentially compose `self` with `f`.
+        fn bind<U, F>(self, f: F) -> Self::Output<U>
+        where F: FnMut(Self::Item) -> Self::Output<U>;
+
+        /// return, wrap `x` in the monad.
+        fn ret(x: Self::Item) -> Self;
+    }
+
+    impl<T> Monad for Option<T> {
+        type Item = T;
+        type Output<U> = Option<U>;
+
+        fn bind<U, F>(self, f: F) -> Option<U> where F: FnMut(T) -> Option<U> {
+            self.and_then(f)
+        }
+
+        fn ret(x: T) -> Option<T> {
+            Some(x)
+        }
+    }
+
+    impl<T, E> Monad for Result<T, E> {
+        type Item = T;
+        type Output<U> = Result<U, E>;
+
+        fn bind<U, F>(self, f: F) -> Result<U, E> where F: FnMut(T) -> Result<U, E> {
+            self.and_then(f)
+        }
+
+        fn ret(x: T) -> Result<T, E> {
+            Ok(x)
+        }
+    }
+
+    impl<T> Monad for Vec<T> {
+        type Item = T;
+        type Output<U> = Vec<U>;
+
+        fn bind<U, F>(self, f: F) -> Vec<U> where F: FnMut(T) -> Vec<U> {
+            self.into_iter().flat_map(f).collect()
+        }
+
+        fn ret(x: T) -> Vec<T> {
+            vec![x]
+        }
+    }
+}
+
+pub mod future {
+    //! Monadic functions for Future
+
+    use std::future::{Future, Ready};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Future returned by `bind`, running `m` then the future returned
+    /// by `f`.
+    pub struct Bind<M, F, U> {
+        first: Option<Pin<Box<M>>>,
+        f: Option<F>,
+        second: Option<Pin<Box<U>>>,
+    }
+
+    // The futures are boxed and `f` is never pinned.
+    impl<M, F, U> Unpin for Bind<M, F, U> {}
+
+    impl<M, F, U> Future for Bind<M, F, U>
+    where M: Future, F: FnOnce(M::Output) -> U, U: Future {
+        type Output = U::Output;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<U::Output> {
+            let this = self.get_mut();
+            if let Some(ref mut first) = this.first {
+                let x = match first.as_mut().poll(cx) {
+                    Poll::Ready(x) => x,
+                    Poll::Pending => return Poll::Pending,
+                };
+                let f = this.f.take().expect("Bind polled after completion");
+                this.second = Some(Box::pin(f(x)));
+            }
+            this.first = None;
+            this.second.as_mut().expect("Bind polled after completion").as_mut().poll(cx)
+        }
+    }
+
+    /// bind for Future, a future running `m` then the future returned
+    /// by `f` on its output.
+    pub fn bind<M, F, U>(m: M, f: F) -> Bind<M, F, U>
+    where M: Future, F: FnOnce(M::Output) -> U, U: Future {
+        Bind { first: Some(Box::pin(m)), f: Some(f), second: None }
+    }
+
+    /// return for Future, an immediately ready future.
+    pub fn ret<T>(x: T) -> Ready<T> {
+        ::std::future::ready(x)
+    }
+}
+
+pub mod lazy {
+    //! Monadic functions for Lazy<T>, a deferred computation
+
+    /// A computation of a `T`, only run when forced.
+    pub struct Lazy<T>(Box<dyn FnOnce() -> T>);
+
+    /// bind for Lazy<T>, a computation forcing `m` and then the result
+    /// of `f`.
+    pub fn bind<T: 'static, U: 'static, F>(m: Lazy<T>, f: F) -> Lazy<U>
+    where F: FnOnce(T) -> Lazy<U> + 'static {
+        Lazy(Box::new(move || force(f(force(m)))))
+    }
+
+    /// return for Lazy<T>, a computation returning `x`.
+    pub fn ret<T: 'static>(x: T) -> Lazy<T> {
+        Lazy(Box::new(move || x))
+    }
+
+    /// Defer the computation `f`.
+    pub fn delay<T, F: FnOnce() -> T + 'static>(f: F) -> Lazy<T> {
+        Lazy(Box::new(f))
+    }
+
+    /// Run the computation.
+    pub fn force<T>(m: Lazy<T>) -> T {
+        (m.0)()
+    }
+}
+
+pub mod pipe {
+    //! Pipelines mixing pure and `Option` returning steps
+
+    type Step<A, B> = Box<dyn Fn(A) -> Option<B>>;
+
+    /// A pipeline from `A` to `Option<B>`, built from pure steps that
+    /// cannot fail and monadic steps that can.
+    pub struct Pipe<A, B>(Step<A, B>);
+
+    impl<A: 'static> Pipe<A, A> {
+        /// The empty pipeline, returning its input.
+        pub fn new() -> Pipe<A, A> {
+            Pipe(Box::new(Some))
+        }
+    }
+
+    impl<A: 'static> Default for Pipe<A, A> {
+        fn default() -> Pipe<A, A> {
+            Pipe::new()
+        }
+    }
+
+    impl<A: 'static, B: 'static> Pipe<A, B> {
+        /// Add a step that cannot fail.
+        pub fn pure<C, F: Fn(B) -> C + 'static>(self, f: F) -> Pipe<A, C> {
+            let g = self.0;
+            Pipe(Box::new(move |a| g(a).map(&f)))
+        }
+
+        /// Add a step that can fail, stopping the pipeline on `None`.
+        pub fn monadic<C, F: Fn(B) -> Option<C> + 'static>(self, f: F) -> Pipe<A, C> {
+            let g = self.0;
+            Pipe(Box::new(move |a| g(a).and_then(&f)))
+        }
+
+        /// Run the pipeline on `a`.
+        pub fn run(&self, a: A) -> Option<B> {
+            (self.0)(a)
+        }
+    }
+}
+
+pub mod btreeset {
+    //! Monadic functions for BTreeSet<T>, a sorted list monad without
+    //! duplicates
+
+    use std::collections::BTreeSet;
+
+    /// bind for BTreeSet<T>, the union of `f` applied on every value
+    pub fn bind<A: Ord, B: Ord, F: Fn(A) -> BTreeSet<B>>(m: BTreeSet<A>, f: F) -> BTreeSet<B> {
+        m.into_iter().flat_map(f).collect()
+    }
+
+    /// return for BTreeSet<T>, a singleton
+    pub fn ret<T: Ord>(x: T) -> BTreeSet<T> {
+        let mut s = BTreeSet::new();
+        s.insert(x);
+        s
+    }
+
+    /// mzero for BTreeSet<T>, the empty set
+    pub fn mzero<T: Ord>() -> BTreeSet<T> {
+        BTreeSet::new()
+    }
+}
+
+pub mod hashset {
+    //! Monadic functions for HashSet<T>, an unordered list monad
+    //! without duplicates
+
+    use std::collections::HashSet;
+    use std::hash::Hash;
+
+    /// bind for HashSet<T>, the union of `f` applied on every value
+    pub fn bind<A, B, F>(m: HashSet<A>, f: F) -> HashSet<B>
+    where A: Eq + Hash, B: Eq + Hash, F: Fn(A) -> HashSet<B> {
+        m.into_iter().flat_map(f).collect()
+    }
+
+    /// return for HashSet<T>, a singleton
+    pub fn ret<T: Eq + Hash>(x: T) -> HashSet<T> {
+        let mut s = HashSet::new();
+        s.insert(x);
+        s
+    }
+
+    /// mzero for HashSet<T>, the empty set
+    pub fn mzero<T: Eq + Hash>() -> HashSet<T> {
+        HashSet::new()
+    }
+}
+
+pub mod deque {
+    //! Monadic functions for VecDeque<T>, a list monad with O(1)
+    //! insertion at both ends
+
+    use std::collections::VecDeque;
+
+    /// bind for VecDeque<T>, the concatenation of `f` applied on every
+    /// value
+    pub fn bind<A, B, F: FnMut(A) -> VecDeque<B>>(m: VecDeque<A>, f: F) -> VecDeque<B> {
+        m.into_iter().flat_map(f).collect()
+    }
+
+    /// return for VecDeque<T>, a deque with one value
+    pub fn ret<T>(x: T) -> VecDeque<T> {
+        let mut m = VecDeque::new();
+        m.push_back(x);
+        m
+    }
+
+    /// mzero for VecDeque<T>, the empty deque
+    pub fn mzero<T>() -> VecDeque<T> {
+        VecDeque::new()
+    }
+
+    /// Insert `x` at the front of `m`
+    pub fn push_front_m<T>(x: T, m: VecDeque<T>) -> VecDeque<T> {
+        let mut m = m;
+        m.push_front(x);
+        m
+    }
+
+    /// Insert `x` at the back of `m`
+    pub fn push_back_m<T>(x: T, m: VecDeque<T>) -> VecDeque<T> {
+        let mut m = m;
+        m.push_back(x);
+        m
+    }
+}
+
+pub mod rope {
+    //! Writer monad logging to a Rope, for building strings
+    //! incrementally
+
+    /// A string made of segments, concatenated only once by
+    /// `into_string`.
+    #[derive(Clone, Debug, Default)]
+    pub struct Rope {
+        segments: Vec<String>,
+        len: usize,
+    }
+
+    impl Rope {
+        /// The empty rope.
+        pub fn new() -> Rope {
+            Rope::default()
+        }
+
+        /// Append `s` at the end of the rope.
+        pub fn push_str(&mut self, s: &str) {
+            self.len += s.len();
+            self.segments.push(s.to_string());
+        }
+
+        /// Append all the segments of `other` at the end of the rope.
+        pub fn append(&mut self, other: Rope) {
+            self.len += other.len;
+            self.segments.extend(other.segments);
+        }
+
+        /// Length of the rope in bytes.
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        /// Whether the rope is empty.
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        /// Concatenate the segments.
+        pub fn into_string(self) -> String {
+            let mut s = String::with_capacity(self.len);
+            for segment in &self.segments {
+                s.push_str(segment);
+            }
+            s
+        }
+
+        fn bytes<'a>(&'a self) -> impl Iterator<Item = u8> + 'a {
+            self.segments.iter().flat_map(|s| s.bytes())
+        }
+    }
+
+    /// Ropes are equal if their contents are, whatever their segments.
+    impl PartialEq for Rope {
+        fn eq(&self, other: &Rope) -> bool {
+            self.len == other.len && self.bytes().eq(other.bytes())
+        }
+    }
+
+    impl Eq for Rope {}
+
+    /// A value of type `A` with a `Rope` log.
+    pub struct Writer<A> {
+        value: A,
+        log: Rope,
+    }
+
+    /// bind for Writer<A>, the log of `m` followed by the log of the
+    /// result of `f`
+    pub fn bind<A, B, F: FnOnce(A) -> Writer<B>>(m: Writer<A>, f: F) -> Writer<B> {
+        let mut log = m.log;
+        let n = f(m.value);
+        log.append(n.log);
+        Writer { value: n.value, log }
+    }
+
+    /// return for Writer<A>, `x` with an empty log
+    pub fn ret<A>(x: A) -> Writer<A> {
+        Writer { value: x, log: Rope::new() }
+    }
+
+    /// Log `s`
+    pub fn tell_str(s: &str) -> Writer<()> {
+        let mut log = Rope::new();
+        log.push_str(s);
+        Writer { value: (), log }
+    }
+
+    /// Log `s` followed by a newline
+    pub fn tell_line(s: &str) -> Writer<()> {
+        let mut log = Rope::new();
+        log.push_str(s);
+        log.push_str("\n");
+        Writer { value: (), log }
+    }
+
+    /// The value and the log concatenated into a `String`
+    pub fn run_rope<A>(w: Writer<A>) -> (A, String) {
+        (w.value, w.log.into_string())
+    }
+}
+
+pub mod try_option {
+    //! Monadic functions for TryOption<T, P>, an Option keeping the
+    //! position of the failure
+
+    /// Either a value or the position where it was not found, as a line
+    /// number or a byte offset.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum TryOption<T, P> {
+        /// A value.
+        Found(T),
+        /// No value, with the position of the failure.
+        NotFound(P),
+    }
+
+    pub use self::TryOption::{Found, NotFound};
+
+    /// bind for TryOption<T, P>, keeping the position of the first
+    /// failure
+    pub fn bind<T, U, P, F>(m: TryOption<T, P>, f: F) -> TryOption<U, P>
+    where F: FnOnce(T) -> TryOption<U, P> {
+        match m {
+            Found(x) => f(x),
+            NotFound(p) => NotFound(p),
+        }
+    }
+
+    /// return for TryOption<T, P>, equivalent to `Found(x)`
+    pub fn ret<T, P>(x: T) -> TryOption<T, P> {
+        Found(x)
+    }
+
+    /// mzero for TryOption<T, P> at `pos`, equivalent to `NotFound(pos)`
+    pub fn mzero_at<T, P>(pos: P) -> TryOption<T, P> {
+        NotFound(pos)
+    }
+
+    /// The position of the failure, `None` if a value was found
+    pub fn position_of<T, P>(m: &TryOption<T, P>) -> Option<&P> {
+        match *m {
+            Found(_) => None,
+            NotFound(ref p) => Some(p),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn option_bind() {
+        use super::option::{bind, ret, mzero};
+        let x = ret(5);
+        assert_eq!(x, Some(5));
+        let x = bind(ret(5), |x| ret(x + 1));
+        assert_eq!(x, Some(6));
+        let x = bind(ret(5), |x| bind(ret(x + 5), |x| ret(x * 2)));
+        assert_eq!(x, Some(20));
+        let x = bind(ret(5i32), |x| bind(if x == 0 { ret(()) } else { mzero() },
+                                         |_| ret(x * 2)));
+        assert_eq!(x, None);
+    }
+
+    #[test]
+    fn option_mdo() {
+        use super::option::{bind, ret, mzero};
+        let x = mdo! {
+            ret ret(5)
+        };
+        assert_eq!(x, Some(5));
+        let x = mdo! {
+            x =<< ret(5);
+            ret ret(x + 1)
+        };
+        assert_eq!(x, Some(6));
+        let x = mdo! {
+            x =<< ret(5);
+            x =<< ret(x + 5);
+            ret ret(x * 2)
+        };
+        assert_eq!(x, Some(20));
+        let x = mdo! {
+            x =<< ret(5i32);
+            when x == 0;
+            ret ret(x * 2)
+        };
+        assert_eq!(x, None);
+    }
+
+    #[test]
+    fn let_type() {
+        let _: i32 = mdo! {
+            let i: i32 = 0;
+            ret i
+        };
+    }
+
+    #[test]
+    fn iter_bind() {
+        use super::iter::{bind, ret, mzero};
+        let l = bind(0..3, move |x| x..3);
+        assert_eq!(l.collect::<Vec<_>>(), vec![0, 1, 2, 1, 2, 2]);
+        let l = bind(0i32..3, move |x|
+                     bind(0..3, move |y| ret(x + y)));
+        assert_eq!(l.collect::<Vec<_>>(), vec![0, 1, 2, 1, 2, 3, 2, 3, 4]);
+        let l = bind(1i32..11, move |z|
+                     bind(1..z + 1, move |y|
+                          bind(1..y + 1, move |x|
+                               bind(if x * x + y * y == z * z { ret(()) }
+                                    else { mzero() },
+                                    move |_|
+                                    ret((x, y, z))))));
+        assert_eq!(l.collect::<Vec<_>>(), vec![(3, 4, 5), (6, 8, 10)]);
+    }
+
+    #[test]
     fn iter_mdo() {
         use super::iter::{bind, ret, mzero};
         let l = mdo! {
-            x =<< 0..3;
-            ret x..3
+            x =<< 0..3;
+            ret x..3
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 1, 2, 1, 2, 2]);
+        let l = mdo! {
+            x =<< 0i32..3;
+            y =<< 0..3;
+            ret ret(x + y)
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 1, 2, 1, 2, 3, 2, 3, 4]);
+        let l = mdo! {
+            z =<< 1i32..11;
+            y =<< 1..z;
+            x =<< 1..y + 1;
+            let test = x * x + y * y == z * z;
+            when test;
+            let res = (x, y, z);
+            ret ret(res)
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![(3, 4, 5), (6, 8, 10)]);
+    }
+
+    #[test]
+    fn iter_ignore() {
+        use super::iter::{bind, ret};
+        let l = mdo! {
+            x =<< 0i32..5;
+            ign 0..2;
+            ret ret(x)
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+    }
+
+    #[test]
+    fn ret_trick() {
+        use super::iter::bind;
+        let l = mdo! {
+            ret =<< 0..5;
+            ret 0..ret
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 0, 1, 0, 1, 2, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn when_trick() {
+        use super::iter::{bind, ret, mzero};
+        let l = mdo! {
+            when =<< 0i32..5;
+            when when != 3;
+            ret ret(when)
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn ign_trick() {
+        use super::iter::{bind, ret};
+        let l = mdo! {
+            ign =<< 0i32..5;
+            ign 0..0;
+            ret ret(ign)
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![]);
+    }
+
+    #[test]
+    fn mdo_doc_example() {
+        use super::iter::{bind, ret, mzero};
+        let l = mdo! {
+            x: i32 =<< 0..5; // assign x to [0, 5[
+            ign 0..2; // duplicate each value
+            when x % 2 == 0; // filter on even values
+            let y = x + 5; // create y
+            ret ret(y + 5) // return y + 5
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![10, 10, 12, 12, 14, 14]);
+    }
+
+    #[test]
+    fn result_map_err_bind() {
+        use std::io::{self, Read};
+        use super::result::{bind, ret, map_err_bind};
+        fn read(mut r: &[u8]) -> io::Result<String> {
+            let mut s = String::new();
+            r.read_to_string(&mut s)?;
+            Ok(s)
+        }
+        fn sum(a: &'static [u8], b: &'static [u8]) -> Result<i32, String> {
+            mdo! {
+                a =<<? read(a), |e| format!("reading a: {}", e);
+                b =<<? read(b), |e| format!("reading b: {}", e);
+                x: i32 =<<? a.trim().parse(), |e| format!("parsing a: {}", e);
+                y: i32 =<<? b.trim().parse(), |e| format!("parsing b: {}", e);
+                z =<< ret(x + y);
+                ret ret(z)
+            }
+        }
+        assert_eq!(sum(b"1\n", b"2\n"), Ok(3));
+        assert_eq!(sum(b"1", b"\xff"),
+                   Err("reading b: stream did not contain valid UTF-8".to_string()));
+        assert_eq!(sum(b"a", b"2"),
+                   Err("parsing a: invalid digit found in string".to_string()));
+    }
+
+    #[test]
+    fn result_partition() {
+        use super::result::partition;
+        let (oks, errs) = partition(vec![Ok(1), Err("a"), Ok(2), Err("b"), Ok(3)]);
+        assert_eq!(oks, vec![1, 2, 3]);
+        assert_eq!(errs, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn iter_windows() {
+        use super::iter::{bind, ret, windows};
+        let l = windows(0..4, 2).collect::<Vec<_>>();
+        assert_eq!(l, vec![vec![0, 1], vec![1, 2], vec![2, 3]]);
+        let l = windows(0..2, 3).collect::<Vec<_>>();
+        assert_eq!(l, Vec::<Vec<i32>>::new());
+        let l = mdo! {
+            w =<< windows("abcd".chars(), 2);
+            ret ret(w.into_iter().collect::<String>())
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec!["ab", "bc", "cd"]);
+
+        // a source yielding again after a None is not resumed
+        let mut n = 0;
+        let flaky = ::std::iter::from_fn(move || { n += 1; if n == 3 { None } else { Some(n) } });
+        let mut w = windows(flaky, 2);
+        assert_eq!(w.next(), Some(vec![1, 2]));
+        assert_eq!(w.next(), None);
+        assert_eq!(w.next(), None);
+    }
+
+    #[test]
+    fn lazy_option_mdo() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use super::lazy_option::{bind, ret, mzero, lazy_option, force};
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        let m = mdo! {
+            x =<< lazy_option(move || { c.set(c.get() + 1); Some(5) });
+            when x > 0;
+            ret ret(x * 2)
+        };
+        assert_eq!(calls.get(), 0);
+        assert_eq!(force(&m), Some(&10));
+        assert_eq!(force(&m), Some(&10));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn lazy_option_mplus() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use super::lazy_option::{lazy_option, mplus, force};
+        let calls = Rc::new(vec![Cell::new(0), Cell::new(0), Cell::new(0), Cell::new(0)]);
+        let rule = |i: usize, res: Option<&'static str>| {
+            let calls = calls.clone();
+            lazy_option(move || { calls[i].set(calls[i].get() + 1); res })
+        };
+        let m = mplus(rule(0, None), mplus(rule(1, Some("b")),
+                                           mplus(rule(2, Some("c")), rule(3, None))));
+        assert!(calls.iter().all(|c| c.get() == 0));
+        assert_eq!(force(&m), Some(&"b"));
+        assert_eq!(calls.iter().map(Cell::get).collect::<Vec<_>>(), vec![1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn try_m() {
+        use std::{env, fs, io, num};
+        #[derive(Debug)]
+        enum Error {
+            Io(io::Error),
+            Parse(num::ParseIntError),
+            Negative(i32),
+        }
+        impl From<io::Error> for Error {
+            fn from(e: io::Error) -> Error { Error::Io(e) }
+        }
+        impl From<num::ParseIntError> for Error {
+            fn from(e: num::ParseIntError) -> Error { Error::Parse(e) }
+        }
+        fn read_positive(name: &str, content: Option<&str>) -> Result<i32, Error> {
+            let path = env::temp_dir().join(format!("mdo-try_m-{}-{}", std::process::id(), name));
+            if let Some(content) = content {
+                fs::write(&path, content).unwrap();
+            }
+            let r = try_m! {
+                s =<< fs::read_to_string(&path);
+                n: i32 =<< s.trim().parse();
+                ign if n < 0 { Err(Error::Negative(n)) } else { Ok(()) };
+                ret Ok(n * 2)
+            };
+            let _ = fs::remove_file(&path);
+            r
+        }
+        assert_eq!(read_positive("ok", Some("21\n")).unwrap(), 42);
+        match read_positive("missing", None) {
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => (),
+            r => panic!("unexpected {:?}", r),
+        }
+        match read_positive("nan", Some("foo")) {
+            Err(Error::Parse(ref e)) if *e.kind() == num::IntErrorKind::InvalidDigit => (),
+            r => panic!("unexpected {:?}", r),
+        }
+        match read_positive("neg", Some("-3")) {
+            Err(Error::Negative(-3)) => (),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn mdo_m() {
+        use super::monad::Monad;
+        let o = mdo_m! {
+            x =<< Some(5);
+            let y = x + 1;
+            z: i32 =<< Some(y * 2);
+            ret Monad::ret(z)
+        };
+        assert_eq!(o, Some(12));
+        let o = mdo_m! {
+            x =<< Some(5);
+            ign None::<()>;
+            ret Monad::ret(x)
+        };
+        assert_eq!(o, None);
+        let l = mdo_m! {
+            x =<< vec![1, 2, 3];
+            y =<< vec![x; x];
+            ret Monad::ret(x * 10 + y)
+        };
+        assert_eq!(l, vec![11, 22, 22, 33, 33, 33]);
+        let r: Result<i32, &str> = mdo_m! {
+            x =<< Ok(1);
+            ign Err::<(), _>("fail");
+            ret Monad::ret(x)
+        };
+        assert_eq!(r, Err("fail"));
+    }
+
+    #[test]
+    fn iter_cycle_take() {
+        use super::iter::cycle_take;
+        assert_eq!(cycle_take(0..2, 5).collect::<Vec<_>>(), vec![0, 1, 0, 1, 0]);
+        assert_eq!(cycle_take(0..0, 5).collect::<Vec<_>>(), vec![]);
+    }
+
+    #[test]
+    fn iter_from_fn_m() {
+        use super::iter::from_fn_m;
+        let (mut a, mut b) = (0u64, 1u64);
+        let fib = from_fn_m(move || {
+            let x = a;
+            a = b;
+            b += x;
+            Some(x)
+        });
+        assert_eq!(fib.take(20).collect::<Vec<_>>(),
+                   vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144, 233, 377, 610,
+                        987, 1597, 2584, 4181]);
+        let mut n = 0;
+        let l = from_fn_m(move || { n += 1; if n <= 3 { Some(n) } else { None } });
+        assert_eq!(l.collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn iter_unfold_m() {
+        use super::iter::unfold_m;
+        let fib = unfold_m((0u64, 1u64), |(a, b)| Some((a, (b, a + b))));
+        assert_eq!(fib.take(20).collect::<Vec<_>>(),
+                   vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144, 233, 377, 610,
+                        987, 1597, 2584, 4181]);
+        let primes = unfold_m((vec![], 2u32), |(mut primes, mut n): (Vec<u32>, u32)| {
+            while primes.iter().any(|p| n % p == 0) {
+                n += 1;
+            }
+            primes.push(n);
+            Some((n, (primes, n + 1)))
+        });
+        assert_eq!(primes.take(20).collect::<Vec<_>>(),
+                   vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61,
+                        67, 71]);
+        let collatz = |n: u64| unfold_m(Some(n), |n: Option<u64>| n.map(|n| {
+            let next = if n == 1 { None } else if n % 2 == 0 { Some(n / 2) } else { Some(3 * n + 1) };
+            (n, next)
+        }));
+        assert_eq!(collatz(27).take(20).collect::<Vec<_>>(),
+                   vec![27, 82, 41, 124, 62, 31, 94, 47, 142, 71, 214, 107, 322, 161, 484, 242,
+                        121, 364, 182, 91]);
+        assert_eq!(collatz(6).collect::<Vec<_>>(), vec![6, 3, 10, 5, 16, 8, 4, 2, 1]);
+    }
+
+    #[test]
+    fn iter_zip_m() {
+        use super::iter::{bind, ret, zip_m, zip_with_m};
+        let l = mdo! {
+            (x, y) =<< zip_m(0..3, "abc".chars());
+            ret ret(format!("{}{}", y, x))
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec!["a0", "b1", "c2"]);
+        assert_eq!(zip_m(0.., 0..4).count(), 4);
+        assert_eq!(zip_m(0..7, 0..4).count(), 4);
+        let l = zip_with_m(|x, y| x * y, 1.., vec![2, 3, 4].into_iter()).collect::<Vec<_>>();
+        assert_eq!(l, vec![2, 6, 12]);
+        assert_eq!(zip_with_m(|x: i32, y: i32| x + y, 0..2, 0..5).count(), 2);
+    }
+
+    #[test]
+    fn option_or_else_m() {
+        use super::option::or_else_m;
+        assert_eq!(or_else_m(None, || Some(3)), Some(3));
+        assert_eq!(or_else_m(Some(1), || -> Option<i32> { panic!("called") }), Some(1));
+        assert_eq!(or_else_m(None::<i32>, || None), None);
+    }
+
+    #[test]
+    fn iter_dedup() {
+        use super::iter::{bind, ret, dedup};
+        assert_eq!(dedup(vec![1, 1, 2, 2, 2, 3].into_iter()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(dedup(vec![1, 2, 1].into_iter()).collect::<Vec<_>>(), vec![1, 2, 1]);
+        let l = dedup(mdo! {
+            x =<< 0i32..4;
+            ign 0..3;
+            ret ret(x / 2)
+        }).collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 1]);
+    }
+
+    #[test]
+    fn iter_windows_m() {
+        use super::iter::windows_m;
+        let data = b"GET /\r\nHost: a\r\n\r\n";
+        let crlf = windows_m(2, |w| if w == b"\r\n" { Some(()) } else { None }, data);
+        assert_eq!(crlf.count(), 3);
+        let data = [1, 2, 3, 2, 5, 6, 7];
+        let l = windows_m(3, |w| {
+            if w[0] < w[1] && w[1] < w[2] { Some(w.iter().sum::<i32>()) } else { None }
+        }, &data).collect::<Vec<_>>();
+        assert_eq!(l, vec![6, 13, 18]);
+        assert_eq!(windows_m(4, |w| Some(w.len()), &[1, 2]).count(), 0);
+    }
+
+    #[test]
+    fn iter_chunks_m() {
+        use super::iter::chunks_m;
+        // frames of [0xaa, id, len_hi, len_lo], with a corrupted one and trailing garbage
+        let frames: Vec<u8> = vec![0xaa, 1, 0, 42, 0x00, 2, 0, 0, 0xaa, 3, 1, 0, 0xaa, 4];
+        let l = chunks_m(4, |f| match *f {
+            [0xaa, id, hi, lo] => Some((id, u16::from(hi) << 8 | u16::from(lo))),
+            _ => None,
+        }, frames).collect::<Vec<_>>();
+        assert_eq!(l, vec![(1, 42), (3, 256)]);
+        let l = chunks_m(2, |c| Some(c.to_vec()), vec![0, 1, 2, 3, 4]).collect::<Vec<_>>();
+        assert_eq!(l, vec![vec![0, 1], vec![2, 3], vec![4]]);
+    }
+
+    #[test]
+    fn transpose() {
+        use super::{option, result};
+        assert_eq!(option::transpose(Some(Ok::<_, ()>(1))), Ok(Some(1)));
+        assert_eq!(option::transpose(Some(Err::<i32, _>(()))), Err(()));
+        assert_eq!(option::transpose(None::<Result<i32, ()>>), Ok(None));
+        assert_eq!(result::transpose(Ok::<_, ()>(Some(1))), Some(Ok(1)));
+        assert_eq!(result::transpose(Ok::<Option<i32>, ()>(None)), None);
+        assert_eq!(result::transpose(Err::<Option<i32>, _>(())), Some(Err(())));
+        for &m in &[Some(Ok(1)), Some(Err(())), None] {
+            assert_eq!(result::transpose(option::transpose(m)), m);
+        }
+        for &m in &[Ok(Some(1)), Ok(None), Err(())] {
+            assert_eq!(option::transpose(result::transpose(m)), m);
+        }
+    }
+
+    #[test]
+    fn result_sequence() {
+        use super::result::{sequence, broadcast};
+        assert_eq!(sequence(vec![Ok::<_, &str>(1), Ok(2)]), Ok(vec![1, 2]));
+        assert_eq!(sequence(vec![Ok(1), Err("a"), Err("b")]), Err("a"));
+        assert_eq!(broadcast(Ok::<_, &str>(vec![1, 2])), vec![Ok(1), Ok(2)]);
+        assert_eq!(broadcast(Err::<Vec<i32>, _>("a")), vec![Err("a")]);
+        let v = vec![Ok::<_, &str>(1), Ok(2), Ok(3)];
+        assert_eq!(broadcast(sequence(v.clone())), v);
+        assert_eq!(sequence(broadcast(Err::<Vec<i32>, _>("a"))), Err("a"));
+    }
+
+    #[test]
+    fn for_arm() {
+        use super::iter::{bind, ret, mzero};
+        let l = mdo! {
+            for z in 1i32..11 {
+                for x in 1..z {
+                    let y0 = x;
+                    for y in y0..z {
+                        when x * x + y * y == z * z;
+                        ret ret((x, y, z))
+                    }
+                }
+            }
+        }.collect::<Vec<_>>();
+        let m = mdo! {
+            z =<< 1i32..11;
+            x =<< 1..z;
+            let y0 = x;
+            y =<< y0..z;
+            when x * x + y * y == z * z;
+            ret ret((x, y, z))
+        }.collect::<Vec<_>>();
+        assert_eq!(l, m);
+        assert_eq!(l, vec![(3, 4, 5), (6, 8, 10)]);
+        let l = mdo! {
+            x =<< 0i32..2;
+            for (i, c) in "ab".chars().enumerate() {
+                ret ret((x, i, c))
+            }
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![(0, 0, 'a'), (0, 1, 'b'), (1, 0, 'a'), (1, 1, 'b')]);
+    }
+
+    #[test]
+    fn iter_product_m() {
+        use super::iter::product_m;
+        let l = product_m(vec![vec![1, 2], vec![3, 4], vec![5, 6]]).collect::<Vec<_>>();
+        assert_eq!(l, vec![vec![1, 3, 5], vec![1, 3, 6], vec![1, 4, 5], vec![1, 4, 6],
+                           vec![2, 3, 5], vec![2, 3, 6], vec![2, 4, 5], vec![2, 4, 6]]);
+        assert_eq!(product_m(Vec::<Vec<i32>>::new()).collect::<Vec<_>>(), vec![vec![]]);
+        assert_eq!(product_m(vec![vec![1, 2], vec![]]).count(), 0);
+        let words = ["a".to_string(), "b".to_string()];
+        let borrowed = vec![vec![words[0].as_str(), words[1].as_str()], vec!["c"]];
+        let l = product_m(borrowed).map(|v| v.concat()).collect::<Vec<_>>();
+        assert_eq!(l, vec!["ac", "bc"]);
+    }
+
+    #[test]
+    fn result_tap_err() {
+        use std::cell::RefCell;
+        use super::result::{bind, ret, tap_err};
+        let log = &RefCell::new(vec![]);
+        let r = mdo! {
+            x =<< tap_err(Ok::<i32, String>(1), |e| log.borrow_mut().push(e.clone()));
+            y: i32 =<< tap_err(Err("bad".to_string()), |e| log.borrow_mut().push(e.clone()));
+            ret ret(x + y)
+        };
+        assert_eq!(r, Err("bad".to_string()));
+        assert_eq!(*log.borrow(), vec!["bad".to_string()]);
+    }
+
+    #[test]
+    fn option_from_predicate() {
+        use super::option::{bind, ret, from_predicate, from_predicate_with};
+        let checked_div = |a: i32, b: i32| mdo! {
+            b =<< from_predicate(b, |&b| b != 0);
+            q =<< from_predicate_with(a, |a| a.checked_div(b));
+            ret ret(q)
+        };
+        assert_eq!(checked_div(7, 2), Some(3));
+        assert_eq!(checked_div(7, 0), None);
+        assert_eq!(checked_div(i32::MIN, -1), None);
+    }
+
+    fn block_on<F: ::std::future::Future>(f: F) -> F::Output {
+        use std::task::{Context, Poll, Waker};
+        let mut f = Box::pin(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(x) = f.as_mut().poll(&mut cx) {
+                return x;
+            }
+        }
+    }
+
+    #[test]
+    fn mdo_async() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+        use super::future::ret;
+        struct YieldOnce<T>(Option<T>, bool);
+        impl<T: Unpin> Future for YieldOnce<T> {
+            type Output = T;
+            fn poll(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<T> {
+                if self.1 {
+                    Poll::Ready(self.0.take().unwrap())
+                } else {
+                    self.1 = true;
+                    Poll::Pending
+                }
+            }
+        }
+        let f = mdo_async! {
+            x =<< YieldOnce(Some(20), false);
+            let y = x + 1;
+            z: i32 =<< ret(y * 2);
+            ign YieldOnce(Some(()), false);
+            ret ret(z)
+        };
+        assert_eq!(block_on(f), 42);
+    }
+
+    #[test]
+    fn result_annotate() {
+        use std::convert::TryFrom;
+        use super::result::{bind, ret, annotate, annotate_with};
+        fn parse(s: &str) -> Result<u8, String> {
+            mdo! {
+                i =<< annotate("parsing int", s.parse::<i32>());
+                b =<< annotate("converting to byte", u8::try_from(i));
+                c =<< annotate_with(|| format!("checking {}", b), if b % 2 == 0 {
+                    Ok(b)
+                } else {
+                    Err("odd number")
+                });
+                ret ret(c)
+            }
+        }
+        let load = |s| annotate("loading config", parse(s));
+        assert_eq!(load("42"), Ok(42));
+        assert_eq!(load("x"),
+                   Err("loading config: parsing int: invalid digit found in string".to_string()));
+        assert_eq!(load("300"),
+                   Err("loading config: converting to byte: out of range integral type \
+                        conversion attempted".to_string()));
+        assert_eq!(load("3"), Err("loading config: checking 3: odd number".to_string()));
+        let mut called = false;
+        assert_eq!(annotate_with(|| { called = true; String::new() }, Ok::<_, &str>(1)), Ok(1));
+        assert!(!called);
+    }
+
+    #[test]
+    fn option_try_sum_product() {
+        use super::option::{try_sum, try_product};
+        assert_eq!(try_sum(vec![Some(1), Some(2), Some(3)]), Some(6));
+        assert_eq!(try_sum(vec![Some(1), None, Some(3)]), None);
+        assert_eq!(try_sum(vec![]), Some(0));
+        assert_eq!(try_product(vec![Some(2), Some(3), Some(4)]), Some(24));
+        assert_eq!(try_product(vec![Some(2), None]), None);
+        assert_eq!(try_product(vec![]), Some(1));
+    }
+
+    #[test]
+    fn iter_size_hint() {
+        use super::iter::{bind, ret, rev_bind, size_hint_bind};
+        let l = bind(0..3, |x| bind(ret(x), |y| 0..y));
+        let (lower, upper) = l.size_hint();
+        assert!(lower <= 3 && upper.is_none_or(|u| u >= 3));
+        assert_eq!(l.count(), 3);
+        let mut l = size_hint_bind(0..3, |x| size_hint_bind(ret(x), |y| 0..y));
+        assert_eq!(l.size_hint(), (3, Some(3)));
+        assert_eq!(l.len(), 3);
+        assert_eq!(l.next(), Some(0));
+        assert_eq!(l.next_back(), Some(1));
+        assert_eq!(l.len(), 1);
+        assert_eq!(l.collect::<Vec<_>>(), vec![0]);
+        let l = size_hint_bind(0..2, |x| (x..).take_while(|&y| y < 2));
+        assert_eq!(l.size_hint(), (0, None));
+        assert_eq!(rev_bind(0..3, |x| 0..x).collect::<Vec<_>>(), vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn wildcard_bind() {
+        use super::iter::{bind, ret};
+        let l = mdo! {
+            x =<< 0i32..3;
+            _ =<< 0..2;
+            ret ret(x)
+        }.collect::<Vec<_>>();
+        let m = mdo! {
+            x =<< 0i32..3;
+            ign 0..2;
+            ret ret(x)
+        }.collect::<Vec<_>>();
+        assert_eq!(l, m);
+        assert_eq!(l, vec![0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn iter_interleave() {
+        use super::iter::interleave;
+        assert_eq!(interleave(0..3, 10..12).collect::<Vec<_>>(), vec![0, 10, 1, 11, 2]);
+        assert_eq!(interleave(0..1, 10..13).collect::<Vec<_>>(), vec![0, 10, 11, 12]);
+        assert_eq!(interleave(0.., 10..12).take(6).collect::<Vec<_>>(), vec![0, 10, 1, 11, 2, 3]);
+    }
+
+    #[test]
+    fn option_flatten() {
+        use super::option::{flatten, flatten_n};
+        assert_eq!(flatten(Some(Some(1))), Some(1));
+        assert_eq!(flatten(Some(None::<i32>)), None);
+        assert_eq!(flatten(None::<Option<i32>>), None);
+        assert_eq!(flatten_n(Some(Some(Some(1)))), Some(1));
+        assert_eq!(flatten_n(Some(Some(None::<i32>))), None);
+        assert_eq!(flatten_n(Some(None::<Option<i32>>)), None);
+        assert_eq!(flatten_n(None::<Option<Option<i32>>>), None);
+    }
+
+    #[test]
+    fn result_flatten() {
+        use super::result::flatten;
+        assert_eq!(flatten(Ok::<Result<i32, &str>, _>(Ok(1))), Ok(1));
+        assert_eq!(flatten(Ok::<Result<i32, &str>, _>(Err("inner"))), Err("inner"));
+        assert_eq!(flatten(Err::<Result<i32, &str>, _>("outer")), Err("outer"));
+    }
+
+    #[test]
+    fn result_flatten_nested() {
+        use std::{io, num};
+        use super::result::flatten_nested;
+        #[derive(Debug)]
+        enum Error {
+            Io(io::ErrorKind),
+            Parse(num::ParseIntError),
+        }
+        impl From<io::Error> for Error {
+            fn from(e: io::Error) -> Error { Error::Io(e.kind()) }
+        }
+        impl From<num::ParseIntError> for Error {
+            fn from(e: num::ParseIntError) -> Error { Error::Parse(e) }
+        }
+        // in-memory files: the name is the content, "missing" is not found
+        let read_to_string = |path: &str| if path == "missing" {
+            Err(io::Error::new(io::ErrorKind::NotFound, "no such file"))
+        } else {
+            Ok(path.to_string())
+        };
+        let read = |path: &str| -> Result<i32, Error> {
+            flatten_nested(read_to_string(path).map(|s| s.trim().parse::<i32>()))
+        };
+        match read("missing") {
+            Err(Error::Io(io::ErrorKind::NotFound)) => (),
+            r => panic!("unexpected {:?}", r),
+        }
+        assert_eq!(read(" 7\n").unwrap(), 7);
+        match read("[package]") {
+            Err(Error::Parse(ref e)) if *e.kind() == num::IntErrorKind::InvalidDigit => (),
+            r => panic!("unexpected {:?}", r),
+        }
+        let r: Result<i32, Error> = flatten_nested(Ok::<_, io::Error>("42".parse()));
+        assert_eq!(r.unwrap(), 42);
+    }
+
+    #[test]
+    fn result_and_or() {
+        use super::result::{and_then_discard, or};
+        assert_eq!(and_then_discard(Ok::<_, &str>(1), Ok::<_, &str>("b")), Ok("b"));
+        assert_eq!(and_then_discard(Ok::<i32, _>(1), Err::<&str, _>("e2")), Err("e2"));
+        assert_eq!(and_then_discard(Err::<i32, _>("e1"), Ok::<_, &str>("b")), Err("e1"));
+        assert_eq!(and_then_discard(Err::<i32, _>("e1"), Err::<&str, _>("e2")), Err("e1"));
+        assert_eq!(or(Ok::<_, &str>(1), Ok::<_, i32>(2)), Ok(1));
+        assert_eq!(or(Ok::<_, &str>(1), Err::<i32, _>(2)), Ok(1));
+        assert_eq!(or(Err::<i32, _>("e1"), Ok::<_, i32>(2)), Ok(2));
+        assert_eq!(or(Err::<i32, _>("e1"), Err::<i32, _>(2)), Err(2));
+    }
+
+    #[test]
+    fn iter_group_by_m() {
+        use super::iter::group_by_m;
+        let words = vec!["apple", "bob", "avocado", "cat", "banana"];
+        let mut groups = group_by_m(|w| w.chars().next(), words.clone().into_iter())
+            .collect::<Vec<_>>();
+        groups.sort();
+        assert_eq!(groups, vec![(Some('a'), vec!["apple", "avocado"]),
+                                (Some('b'), vec!["bob", "banana"]),
+                                (Some('c'), vec!["cat"])]);
+        let mut all = groups.into_iter().flat_map(|(_, g)| g).collect::<Vec<_>>();
+        all.sort();
+        let mut expected = words;
+        expected.sort();
+        assert_eq!(all, expected);
+
+        struct Transaction { date: &'static str, amount: i32 }
+        let transactions = vec![
+            Transaction { date: "2014-11-02", amount: 10 },
+            Transaction { date: "2014-11-01", amount: -5 },
+            Transaction { date: "2014-11-02", amount: 7 },
+        ];
+        let mut totals = group_by_m(|t| t.date, transactions.into_iter())
+            .map(|(d, ts)| (d, ts.len(), ts.iter().map(|t| t.amount).sum::<i32>()))
+            .collect::<Vec<_>>();
+        totals.sort();
+        assert_eq!(totals, vec![("2014-11-01", 1, -5), ("2014-11-02", 2, 17)]);
+    }
+
+    #[test]
+    fn iter_bind3() {
+        use super::iter::{ret, mzero, bind2, bind3};
+        let l = bind3(1i32..11, 1..11, 1..11, |z, x, y| {
+            if x <= y && y < z && x * x + y * y == z * z { ret((x, y, z)) } else { mzero() }
+        }).collect::<Vec<_>>();
+        assert_eq!(l, vec![(3, 4, 5), (6, 8, 10)]);
+        let l = bind2(0..2, "ab".chars(), |x, c| ret(format!("{}{}", c, x))).collect::<Vec<_>>();
+        assert_eq!(l, vec!["a0", "b0", "a1", "b1"]);
+    }
+
+    #[test]
+    fn iter_dedup_by_key_m() {
+        use super::iter::{bind, ret, mzero, dedup, dedup_by_key_m};
+        let triples = || mdo! {
+            z =<< 1i32..31;
+            x =<< 1..z;
+            y =<< x..z;
+            when x * x + y * y == z * z;
+            ign 0..2;
+            ret ret((x, y, z))
+        };
+        assert_eq!(triples().count(), 22);
+        let l = dedup(triples()).collect::<Vec<_>>();
+        assert_eq!(l, vec![(3, 4, 5), (6, 8, 10), (5, 12, 13), (9, 12, 15), (8, 15, 17),
+                           (12, 16, 20), (7, 24, 25), (15, 20, 25), (10, 24, 26), (20, 21, 29),
+                           (18, 24, 30)]);
+        let l = dedup_by_key_m(|t| t.2, triples()).collect::<Vec<_>>();
+        assert_eq!(l, vec![(3, 4, 5), (6, 8, 10), (5, 12, 13), (9, 12, 15), (8, 15, 17),
+                           (12, 16, 20), (7, 24, 25), (10, 24, 26), (20, 21, 29),
+                           (18, 24, 30)]);
+    }
+
+    #[test]
+    fn iter_take_drop_while_m() {
+        use std::collections::HashMap;
+        use super::iter::{take_while_m, drop_while_m};
+        let kinds: HashMap<char, bool> =
+            vec![('a', true), ('b', true), (' ', false), ('c', true)].into_iter().collect();
+        let is_word = |c: &char| kinds.get(c).cloned();
+        let l = take_while_m(&is_word, "ab cab".chars()).collect::<String>();
+        assert_eq!(l, "ab");
+        let l = take_while_m(&is_word, "abzab".chars()).collect::<String>();
+        assert_eq!(l, "ab");
+        let l = drop_while_m(&is_word, "ab cab".chars()).collect::<String>();
+        assert_eq!(l, " cab");
+        let l = drop_while_m(&is_word, "ab cz".chars()).collect::<String>();
+        assert_eq!(l, " cz");
+        let l = drop_while_m(&is_word, "abzab".chars()).collect::<String>();
+        assert_eq!(l, "");
+    }
+
+    #[test]
+    fn option_zip_with() {
+        use super::option::zip_with;
+        let add = |a: i32, b: i32| a + b;
+        assert_eq!(zip_with(Some(1), Some(2), add), Some(3));
+        assert_eq!(zip_with(Some(1), None, add), None);
+        assert_eq!(zip_with(None, Some(2), add), None);
+        assert_eq!(zip_with(None, None, add), None);
+    }
+
+    #[test]
+    fn alternatives() {
+        use std::cell::Cell;
+        use super::{iter, option};
+        let calls = Cell::new(0);
+        let fallback = || { calls.set(calls.get() + 1); Some(0) };
+        assert_eq!(option::or_else_m(Some(1), fallback), Some(1));
+        assert_eq!(option::or_else_m(Some(2), fallback), Some(2));
+        assert_eq!(calls.get(), 0);
+        assert_eq!(option::or_else_m(None, fallback), Some(0));
+        assert_eq!(calls.get(), 1);
+
+        let calls = Cell::new(0);
+        let count = |x| { calls.set(calls.get() + 1); x };
+        let mut l = iter::alt_m((0..2).map(&count), (10..12).map(&count));
+        assert_eq!(l.next(), Some(0));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(l.collect::<Vec<_>>(), vec![1, 10, 11]);
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn result_context() {
+        use super::result::{bind, ret, context};
+        let parse = |s: &str| mdo! {
+            x: i32 =<< context(s.parse(), || format!("parsing {:?}", s));
+            y =<< context(x.checked_mul(1000).ok_or("overflow"), || "scaling");
+            ret ret(y)
+        };
+        assert_eq!(parse("12"), Ok(12000));
+        assert_eq!(parse("x"), Err("parsing \"x\": invalid digit found in string".to_string()));
+        assert_eq!(parse("9999999"), Err("scaling: overflow".to_string()));
+        let r = context(Ok::<_, &str>(1), || -> &str { panic!("called") });
+        assert_eq!(r, Ok(1));
+    }
+
+    #[test]
+    fn iter_pairwise() {
+        use super::iter::{bind, ret, pairwise};
+        assert_eq!(pairwise(0..4).collect::<Vec<_>>(), vec![(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(pairwise(0..1).count(), 0);
+        assert_eq!(pairwise(0..0).count(), 0);
+        let l = mdo! {
+            (a, b) =<< pairwise(vec![1, 4, 9, 16].into_iter());
+            ret ret(b - a)
+        }.collect::<Vec<_>>();
+        assert_eq!(l, vec![3, 5, 7]);
+    }
+
+    #[test]
+    fn option_and_m_zip_options() {
+        use super::option::{and_m, zip_options};
+        assert_eq!(and_m(Some(1), Some("b")), Some("b"));
+        assert_eq!(and_m(None::<i32>, Some("b")), None);
+        assert_eq!(and_m(Some(1), None::<&str>), None);
+        assert_eq!(zip_options(Some(1), Some("b")), Some((1, "b")));
+        assert_eq!(zip_options(None::<i32>, Some("b")), None);
+        assert_eq!(zip_options(Some(1), None::<&str>), None);
+        assert_eq!(zip_options(None::<i32>, None::<&str>), None);
+    }
+
+    #[test]
+    fn bimap() {
+        use super::{option, result};
+        #[derive(Debug, PartialEq)]
+        enum Error { Parse(String), Range(i64) }
+        let parse = |s: &str| result::bimap(|x: i64| x * 2,
+                                            |e: ::std::num::ParseIntError| Error::Parse(e.to_string()),
+                                            s.parse());
+        let check = |x: i64| result::bimap(|x| x + 1, Error::Range,
+                                          if x < 100 { Ok(x) } else { Err(x) });
+        assert_eq!(parse("21"), Ok(42));
+        assert_eq!(parse("a"), Err(Error::Parse("invalid digit found in string".to_string())));
+        assert_eq!(check(42), Ok(43));
+        assert_eq!(check(420), Err(Error::Range(420)));
+        assert_eq!(option::bimap(|x: i32| x.to_string(), || "none".to_string(), Some(1)), "1");
+        assert_eq!(option::bimap(|x: i32| x.to_string(), || "none".to_string(), None), "none");
+    }
+
+    #[test]
+    fn option_tap() {
+        use std::cell::RefCell;
+        use super::option::{bind, ret, tap};
+        let log = &RefCell::new(vec![]);
+        let o = mdo! {
+            x =<< ret(1);
+            ign tap(Some(x + 1), |y| log.borrow_mut().push(*y));
+            ign tap(None::<i32>, |y| log.borrow_mut().push(*y));
+            ret ret(x)
+        };
+        assert_eq!(o, None);
+        assert_eq!(*log.borrow(), vec![2]);
+        assert_eq!(tap(Some(3), |_| ()), Some(3));
+    }
+
+    #[test]
+    fn tap_calls() {
+        use std::cell::Cell;
+        use super::option::tap;
+        use super::result::tap_err;
+        let calls = Cell::new(0);
+        let count = |_: &_| calls.set(calls.get() + 1);
+        assert_eq!(tap(Some("a"), count), Some("a"));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(tap(None::<&str>, count), None);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(tap_err(Err::<(), _>("e"), count), Err("e"));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(tap_err(Ok::<_, &str>(()), count), Ok(()));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn iter_span_m() {
+        use super::iter::{span_m, span_option_m};
+        let (odds, rest) = span_m(|x| x % 2 == 1, vec![1, 3, 4, 5, 6].into_iter());
+        assert_eq!((odds, rest), (vec![1, 3], vec![4, 5, 6]));
+        assert_eq!(span_m(|_| true, 0..3), (vec![0, 1, 2], vec![]));
+        let (key, value) = span_m(|&c| c != '=', "key=value".chars());
+        assert_eq!(key.into_iter().collect::<String>(), "key");
+        assert_eq!(value.into_iter().collect::<String>(), "=value");
+        let digit = |c: &char| if c.is_ascii_alphanumeric() { Some(c.is_ascii_digit()) } else { None };
+        assert_eq!(span_option_m(digit, "12a!".chars()), Some((vec!['1', '2'], vec!['a', '!'])));
+        assert_eq!(span_option_m(digit, "1!a".chars()), None);
+    }
+
+    #[test]
+    fn lazy() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        use super::lazy::{bind, ret, delay, force};
+        let log = Rc::new(RefCell::new(vec![]));
+        let (l1, l2) = (log.clone(), log.clone());
+        let m = mdo! {
+            x =<< delay(move || { l1.borrow_mut().push("x"); 20 });
+            y =<< delay(move || { l2.borrow_mut().push("y"); x + 1 });
+            ret ret(y * 2)
+        };
+        assert!(log.borrow().is_empty());
+        assert_eq!(force(m), 42);
+        assert_eq!(*log.borrow(), vec!["x", "y"]);
+    }
+
+    #[test]
+    fn iter_flat_map_m() {
+        use super::iter::{bind, flat_map_m};
+        let l = flat_map_m(0..4, |x| 0..x).collect::<Vec<_>>();
+        assert_eq!(l, bind(0..4, |x| 0..x).collect::<Vec<_>>());
+        assert_eq!(l, (0..4).flat_map(|x| 0..x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn iter_scanl_m() {
+        use super::iter::scanl_m;
+        let l = scanl_m(|s, x| Some(s + x), 0, 1..5).collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 1, 3, 6, 10]);
+        assert_eq!(scanl_m(|s, x| Some(s + x), 7, 0..0).collect::<Vec<_>>(), vec![7]);
+        let l = scanl_m(|s: u8, x| s.checked_add(x), 0, (100..).map(|_| 100)).collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 100, 200]);
+    }
+
+    #[test]
+    fn result_all_errors() {
+        use super::result::{traverse, sequence_all_errors, traverse_all_errors};
+        let parse = |s: &str| s.parse::<i32>().map_err(|_| s.to_string());
+        let input = vec!["1", "a", "2", "b", "c"];
+        assert_eq!(traverse(parse, input.clone()), Err("a".to_string()));
+        assert_eq!(traverse_all_errors(parse, input.clone()),
+                   Err(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+        assert_eq!(traverse_all_errors(parse, vec!["1", "2"]), Ok(vec![1, 2]));
+        assert_eq!(traverse(parse, vec!["1", "2"]), Ok(vec![1, 2]));
+        assert_eq!(sequence_all_errors(input.into_iter().map(parse)).unwrap_err().len(), 3);
+        assert_eq!(sequence_all_errors(Vec::<Result<i32, ()>>::new()), Ok(vec![]));
+    }
+
+    #[test]
+    fn result_ensure() {
+        use std::cell::Cell;
+        use super::result::{bind, ret, ensure};
+        #[derive(Debug, PartialEq)]
+        struct NotPositive(i32);
+        let calls = &Cell::new(0);
+        let check = |x: i32| mdo! {
+            ign ensure(x > 0, || { calls.set(calls.get() + 1); NotPositive(x) });
+            ret ret(x * 2)
+        };
+        assert_eq!(check(2), Ok(4));
+        assert_eq!(calls.get(), 0);
+        assert_eq!(check(-1), Err(NotPositive(-1)));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn option_defaults_and_conversions() {
+        use super::option::{or_default, or_else_default, to_result_with};
+        use super::result::ok_or_log;
+        assert_eq!(or_default(Some(3)), 3);
+        assert_eq!(or_default::<i32>(None), 0);
+        assert_eq!(or_else_default(|| 7, None), 7);
+        assert_eq!(or_else_default(|| 7, Some(1)), 1);
+        assert_eq!(to_result_with(|| "none", Some(1)), Ok(1));
+        assert_eq!(to_result_with(|| "none", None::<i32>), Err("none"));
+        assert_eq!(ok_or_log(Ok::<_, ()>(2)), Some(2));
+        assert_eq!(ok_or_log(Err::<i32, _>("oops")), None);
+    }
+
+    #[test]
+    fn const_ret() {
+        const SOME: Option<i32> = super::option::ret(5);
+        const NONE: Option<i32> = super::option::mzero();
+        const OK: Result<i32, ()> = super::result::ret(5);
+        assert_eq!(SOME, Some(5));
+        assert_eq!(NONE, None);
+        assert_eq!(OK, Ok(5));
+    }
+
+    #[test]
+    fn iter_stepped_and_count_from() {
+        use super::iter::{count_from, stepped};
+        assert_eq!(stepped(0..10, 3).collect::<Vec<_>>(), vec![0, 3, 6, 9]);
+        assert_eq!(count_from(5u8).take(3).collect::<Vec<_>>(), vec![5, 6, 7]);
+    }
+
+    #[test]
+    fn mdo_map_option_result() {
+        {
+            use super::option::{bind, ret};
+            assert_eq!(mdo_map!(|a, b, c| a + b + c; Some(1), Some(2), Some(3)), Some(6));
+            assert_eq!(mdo_map!(|a, b, c| a + b + c; Some(1), None::<i32>, Some(3)), None);
+            assert_eq!(mdo_map!(|a: i32| a * 2; Some(4)), Some(8));
+        }
+        {
+            use super::result::{bind, ret};
+            let ok: Result<i32, &str> = mdo_map!(|a, b, c| a * b * c; Ok(2), Ok(3), Ok(4));
+            assert_eq!(ok, Ok(24));
+            let err: Result<i32, &str> = mdo_map!(|a, b, c| a * b * c; Ok(2), Err::<i32, _>("b"), Err::<i32, _>("c"));
+            assert_eq!(err, Err("b"));
+        }
+    }
+
+    #[test]
+    fn mdo_map_iter() {
+        use super::iter::{bind, ret};
+        let l = mdo_map!(|a, b| (a, b); 0..2, vec!['x', 'y'].into_iter()).collect::<Vec<_>>();
+        assert_eq!(l, vec![(0, 'x'), (0, 'y'), (1, 'x'), (1, 'y')]);
+    }
+
+    #[test]
+    fn option_transpose_iter() {
+        use super::option::{transpose_from_iter, transpose_to_iter};
+        let v = transpose_to_iter(Some(1..3)).collect::<Vec<_>>();
+        assert_eq!(v, vec![Some(1), Some(2)]);
+        let v = transpose_to_iter(None::<::std::ops::Range<i32>>).collect::<Vec<_>>();
+        assert_eq!(v, vec![None]);
+        assert_eq!(transpose_to_iter(Some(0..0)).count(), 0);
+        let v = transpose_from_iter(vec![Some(1), None, Some(3)].into_iter()).collect::<Vec<_>>();
+        assert_eq!(v, vec![1, 3]);
+        assert_eq!(transpose_from_iter(Vec::<Option<i32>>::new().into_iter()).count(), 0);
+    }
+
+    #[test]
+    fn option_xor() {
+        use super::option::xor;
+        assert_eq!(xor(Some(1), None), Some(1));
+        assert_eq!(xor(None, Some(2)), Some(2));
+        assert_eq!(xor(Some(1), Some(2)), None);
+        assert_eq!(xor::<i32>(None, None), None);
+    }
+
+    #[test]
+    fn mdo_let() {
+        use std::collections::HashMap;
+        use super::option::{bind, ret};
+        let mut map = HashMap::new();
+        map.insert("a", "1,22,333");
+        let lens = |key| mdo_let!(
+            k <- map.get(key),
+            v <- k.split(',').nth(1);
+            v.chars().rev().collect::<String>().len()
+        );
+        let lens_mdo = |key| mdo! {
+            k =<< map.get(key);
+            v =<< k.split(',').nth(1);
+            ret ret(v.chars().rev().collect::<String>().len())
+        };
+        assert_eq!(lens("a"), Some(2));
+        assert_eq!(lens("a"), lens_mdo("a"));
+        assert_eq!(lens("b"), None);
+        assert_eq!(lens("b"), lens_mdo("b"));
+        let r: Result<i32, ::std::num::ParseIntError> =
+            mdo_let!(x <- "4".parse::<i32>(), y <- "5".parse::<i32>(); x * y);
+        assert_eq!(r, Ok(20));
+    }
+
+    #[test]
+    fn pipe() {
+        use super::pipe::Pipe;
+        let p = Pipe::new()
+            .monadic(|s: &str| s.trim().parse::<i32>().ok())
+            .pure(|x| x * 2)
+            .monadic(|x| if x < 100 { Some(x) } else { None })
+            .pure(|x| format!("<{}>", x));
+        assert_eq!(p.run(" 21 "), Some("<42>".to_string()));
+        assert_eq!(p.run("abc"), None);
+        assert_eq!(p.run("50"), None);
+        assert_eq!(Pipe::new().run(1), Some(1));
+    }
+
+    #[test]
+    fn lift_option_iter() {
+        use super::option::lift_iter;
+        use super::iter::{bind, ret, lift_option};
+        assert_eq!(lift_iter(Some(3)).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(lift_iter(None::<i32>).collect::<Vec<_>>(), vec![]);
+        let l = mdo! {
+            s =<< vec!["1", "x", "3"].into_iter();
+            x =<< lift_option(s.parse::<i32>().ok());
+            ret ret(x * 10)
         }.collect::<Vec<_>>();
-        assert_eq!(l, vec![0, 1, 2, 1, 2, 2]);
+        assert_eq!(l, vec![10, 30]);
+    }
+
+    #[test]
+    fn iter_bind_by_ref() {
+        use super::iter::{bind, ret};
+        let v = &vec![1, 2, 3];
         let l = mdo! {
-            x =<< 0i32..3;
-            y =<< 0..3;
+            x =<< v;
+            y =<< &v[1..];
+            ret ret(x * y)
+        }.sum::<i32>();
+        assert_eq!(l, 30);
+        assert_eq!(*v, vec![1, 2, 3]);
+        assert_eq!(mdo! { x =<< &vec![1, 2, 3]; ret ret(*x) }.sum::<i32>(), 6);
+    }
+
+    #[test]
+    fn btreeset_sums() {
+        use std::collections::BTreeSet;
+        use super::btreeset::{bind, ret, mzero};
+        let xs: BTreeSet<i32> = vec![1, 2, 3].into_iter().collect();
+        let ys: BTreeSet<i32> = vec![10, 20].into_iter().collect();
+        let sums = mdo! {
+            x =<< xs.clone();
+            y =<< ys.clone();
             ret ret(x + y)
-        }.collect::<Vec<_>>();
-        assert_eq!(l, vec![0, 1, 2, 1, 2, 3, 2, 3, 4]);
-        let l = mdo! {
+        };
+        assert_eq!(sums.into_iter().collect::<Vec<_>>(), vec![11, 12, 13, 21, 22, 23]);
+        let dups = mdo! {
+            x =<< xs.clone();
+            y =<< xs.clone();
+            when x != y;
+            ret ret(x + y)
+        };
+        assert_eq!(dups.into_iter().collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn hashset_triples() {
+        use std::collections::HashSet;
+        use super::hashset::{bind, ret, mzero};
+        let range = |a, b| (a..b).collect::<HashSet<i32>>();
+        let triples = mdo! {
+            z =<< range(1, 11);
+            y =<< range(1, z);
+            x =<< range(1, y + 1);
+            when x * x + y * y == z * z;
+            ret ret((x, y, z))
+        };
+        assert_eq!(triples, vec![(3, 4, 5), (6, 8, 10)].into_iter().collect());
+        let parities = mdo! {
+            x =<< range(0, 10);
+            ret ret(x % 2)
+        };
+        assert_eq!(parities, range(0, 2));
+    }
+
+    #[test]
+    fn result_fold_ref() {
+        use super::result::fold_ref;
+        let sum = |xs: &[i32]| fold_ref(0, xs, |acc, &x| {
+            if x < 0 { Err(x) } else { Ok(acc + x) }
+        });
+        assert_eq!(sum(&[1, 2, 3]), Ok(6));
+        assert_eq!(sum(&[1, -2, 3, -4]), Err(-2));
+        assert_eq!(sum(&[]), Ok(0));
+    }
+
+    #[test]
+    fn iter_bind_while() {
+        use super::iter::bind_while;
+        let calls = ::std::cell::Cell::new(0);
+        let l = bind_while(0..100, |x| {
+            calls.set(calls.get() + 1);
+            if x < 5 { Some(vec![x; 2]) } else { None }
+        }).collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+        assert_eq!(calls.get(), 6);
+    }
+
+    #[test]
+    fn deque_bfs_dfs() {
+        use std::collections::VecDeque;
+        use super::deque::{bind, ret, mzero, push_back_m, push_front_m};
+        // binary tree 1 -> 2, 3; 2 -> 4, 5; 3 -> 6, 7
+        let children = |n: u32| if n < 4 { push_back_m(2 * n + 1, ret(2 * n)) } else { mzero() };
+
+        let mut bfs = vec![];
+        let mut frontier = ret(1);
+        while !frontier.is_empty() {
+            bfs.extend(frontier.iter().cloned());
+            frontier = mdo! {
+                n =<< frontier;
+                c =<< children(n);
+                ret ret(c)
+            };
+        }
+        assert_eq!(bfs, vec![1, 2, 3, 4, 5, 6, 7]);
+
+        let mut dfs_deque = vec![];
+        let mut stack: VecDeque<u32> = ret(1);
+        while let Some(n) = stack.pop_front() {
+            dfs_deque.push(n);
+            stack = children(n).into_iter().rev().fold(stack, |s, c| push_front_m(c, s));
+        }
+
+        let mut dfs_vec = vec![];
+        let mut stack = vec![1];
+        while let Some(n) = stack.pop() {
+            dfs_vec.push(n);
+            stack.extend(children(n).into_iter().rev());
+        }
+        assert_eq!(dfs_deque, vec![1, 2, 4, 5, 3, 6, 7]);
+        assert_eq!(dfs_deque, dfs_vec);
+    }
+
+    #[test]
+    fn rope_eq() {
+        use super::rope::Rope;
+        let mut ab = Rope::new();
+        ab.push_str("ab");
+        let mut a_b = Rope::new();
+        a_b.push_str("a");
+        a_b.push_str("b");
+        assert_eq!(ab, a_b);
+        a_b.push_str("");
+        assert_eq!(ab, a_b);
+        a_b.push_str("c");
+        assert!(ab != a_b);
+        assert_eq!(Rope::new(), Rope::default());
+    }
+
+    #[test]
+    fn rope_writer() {
+        use super::rope::{bind, ret, tell_str, tell_line, run_rope};
+        let w = mdo! {
+            ign tell_line("header");
+            let x = 20;
+            ign tell_str("x = ");
+            ign tell_str(&x.to_string());
+            ret ret(x * 2)
+        };
+        assert_eq!(run_rope(w), (40, "header\nx = 20".to_string()));
+
+        let w = (0..10_000).fold(ret(0), |w, i| mdo! {
+            n =<< w;
+            ign tell_str(if i % 2 == 0 { "ab" } else { "c" });
+            ret ret(n + 1)
+        });
+        let mut direct = String::new();
+        for i in 0..10_000 {
+            direct.push_str(if i % 2 == 0 { "ab" } else { "c" });
+        }
+        let (n, s) = run_rope(w);
+        assert_eq!(n, 10_000);
+        assert_eq!(s.len(), 15_000);
+        assert_eq!(s, direct);
+    }
+
+    #[test]
+    fn option_count_some() {
+        use super::option::count_some;
+        assert_eq!(count_some(vec![Some(1), None, Some(3), None, Some(5)]), 3);
+        assert_eq!(count_some(Vec::<Option<i32>>::new()), 0);
+    }
+
+    #[test]
+    fn iter_unzip() {
+        use super::iter::{bind, ret, mzero, unzip};
+        let (legs, hyps) = unzip(mdo! {
             z =<< 1i32..11;
             y =<< 1..z;
             x =<< 1..y + 1;
-            let test = x * x + y * y == z * z;
-            when test;
-            let res = (x, y, z);
-            ret ret(res)
-        }.collect::<Vec<_>>();
-        assert_eq!(l, vec![(3, 4, 5), (6, 8, 10)]);
+            when x * x + y * y == z * z;
+            ret ret(((x, y), z))
+        });
+        assert_eq!(legs, vec![(3, 4), (6, 8)]);
+        assert_eq!(hyps, vec![5, 10]);
     }
 
     #[test]
-    fn iter_ignore() {
-        use super::iter::{bind, ret};
-        let l = mdo! {
-            x =<< 0i32..5;
-            ign 0..2;
+    fn try_option_lookups() {
+        use std::collections::HashMap;
+        use super::try_option::{bind, ret, mzero_at, position_of, Found, TryOption};
+        let mut table = HashMap::new();
+        table.insert("main", "init");
+        table.insert("init", "config");
+        table.insert("config", "env");
+        table.insert("env", "path");
+        table.insert("path", "home");
+        let table = &table;
+        let lookup = |name: &str, pos: usize| -> TryOption<&'static str, usize> {
+            table.get(name).map_or_else(|| mzero_at(pos), |&v| ret(v))
+        };
+        let resolve = |start| mdo! {
+            a =<< lookup(start, 1);
+            b =<< lookup(a, 2);
+            c =<< lookup(b, 3);
+            d =<< lookup(c, 4);
+            e =<< lookup(d, 5);
+            ret ret(e)
+        };
+        assert_eq!(resolve("main"), Found("home"));
+        assert_eq!(position_of(&resolve("main")), None);
+        assert_eq!(position_of(&resolve("init")), Some(&5));
+        assert_eq!(position_of(&resolve("env")), Some(&3));
+        assert_eq!(position_of(&resolve("nope")), Some(&1));
+    }
+
+    #[test]
+    fn iter_chunked_bind() {
+        use super::iter::chunked_bind;
+        let sums = chunked_bind(3, |c| Some(c.iter().sum::<i32>()), 1..7).collect::<Vec<_>>();
+        assert_eq!(sums, vec![6, 15]);
+        let chunks = chunked_bind(3, |c| vec![c], 1..9).collect::<Vec<_>>();
+        assert_eq!(chunks, vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8]]);
+        assert_eq!(chunked_bind(2, |c| c, 0..0).count(), 0);
+    }
+
+    #[test]
+    fn option_result_bridges() {
+        use super::option::from_result;
+        use super::result::{bind, ret, from_option};
+        assert_eq!(from_result(Ok::<_, ()>(1)), Some(1));
+        assert_eq!(from_result(Err::<i32, _>("e")), None);
+        let r = mdo! {
+            x =<< from_option(Some(2), "none");
+            y =<< from_option(None::<i32>, "none");
+            ret ret(x + y)
+        };
+        assert_eq!(r, Err("none"));
+        assert_eq!(from_option(Some(2), "none"), Ok(2));
+    }
+
+    #[test]
+    fn iter_scan_m() {
+        use super::iter::scan_m;
+        let sums = scan_m(1..=4, 0, |acc, x| { *acc += x; *acc }).collect::<Vec<_>>();
+        assert_eq!(sums, vec![1, 3, 6, 10]);
+    }
+
+    #[test]
+    fn iter_cycle_m() {
+        use super::iter::{cycle_m, take_cycles};
+        let l = take_cycles(4, vec![1, 2, 3].into_iter()).collect::<Vec<_>>();
+        assert_eq!(l, vec![1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2, 3]);
+        assert_eq!(take_cycles(0, 0..3).count(), 0);
+        assert_eq!(take_cycles(3, 0..0).count(), 0);
+        assert_eq!(cycle_m(0..0, |_, it| Some(it)).count(), 0);
+        let l = cycle_m(0..3, |n, it| Some(n as i32..it.end)).collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 1, 2, 1, 2, 2]);
+        // stop once the cycle scaled values reach 10
+        let l = cycle_m(1..4, |n, it| {
+            if it.clone().map(|x| x * n).max()? < 10 { Some(it) } else { None }
+        }).collect::<Vec<_>>();
+        assert_eq!(l, vec![1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2, 3]);
+        // once finished, `f` is not called again
+        let calls = ::std::cell::Cell::new(0);
+        let mut l = cycle_m(0..1, |_, it| {
+            calls.set(calls.get() + 1);
+            if calls.get() == 2 { None } else { Some(it) }
+        });
+        assert_eq!(l.next(), Some(0));
+        assert_eq!(l.next(), None);
+        assert_eq!(l.next(), None);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn result_map_both() {
+        use super::result::{bimap, map_both};
+        let checks: Vec<Result<&str, &str>> = vec![Ok("ok"), Err("too long"), Err("empty")];
+        let normalized = checks.into_iter()
+            .map(|r| map_both(|s: &str| s.to_uppercase(), r))
+            .collect::<Vec<_>>();
+        assert_eq!(normalized, vec![Ok("OK".to_string()),
+                                    Err("TOO LONG".to_string()),
+                                    Err("EMPTY".to_string())]);
+        let parsed: Vec<Result<i32, ::std::num::ParseIntError>> = vec!["1".parse(), "x".parse()];
+        let uniform = parsed.into_iter()
+            .map(|r| bimap(|x| x * 2, |e| format!("error: {}", e), r))
+            .collect::<Vec<_>>();
+        assert_eq!(uniform, vec![Ok(2), Err("error: invalid digit found in string".to_string())]);
+    }
+
+    #[test]
+    fn option_pipe() {
+        use super::option::pipe;
+        fn trim(s: String) -> Option<String> { Some(s.trim().to_string()) }
+        fn non_empty(s: String) -> Option<String> { if s.is_empty() { None } else { Some(s) } }
+        fn upper(s: String) -> Option<String> { Some(s.to_uppercase()) }
+        let steps: &[fn(String) -> Option<String>] = &[trim, non_empty, upper];
+        assert_eq!(pipe(Some(" mdo ".to_string()), steps), Some("MDO".to_string()));
+        assert_eq!(pipe(Some("   ".to_string()), steps), None);
+        assert_eq!(pipe(None, steps), None);
+        assert_eq!(pipe(Some(1), &[]), Some(1));
+    }
+
+    #[test]
+    fn iter_try_map_collect() {
+        use std::cell::Cell;
+        use super::iter::try_map_collect;
+        let calls = Cell::new(0);
+        let r = try_map_collect(vec!["1", "2", "x", "4"], |s| {
+            calls.set(calls.get() + 1);
+            s.parse::<i32>()
+        });
+        assert!(r.is_err());
+        assert_eq!(calls.get(), 3);
+        assert_eq!(try_map_collect(vec!["1", "2"], |s| s.parse::<i32>()), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn iter_peek_bind() {
+        use super::iter::{bind, ret, mzero, peek_bind};
+        let input = &mut vec![7, 9, 6, 2, 8].into_iter().peekable();
+        let mut big = vec![];
+        loop {
+            let elem = mdo! {
+                elem =<< peek_bind(|next| if *next > 5 { ret(*next) } else { mzero() }, input);
+                ret ret(elem)
+            }.next();
+            match elem {
+                Some(x) => big.push(x),
+                None => break,
+            }
+        }
+        assert_eq!(big, vec![7, 9, 6]);
+        assert_eq!(input.collect::<Vec<_>>(), vec![2, 8]);
+    }
+
+    #[cfg(feature = "catching")]
+    #[test]
+    fn option_catching() {
+        use super::option::catching;
+        let div = |a: i32, b: i32| catching(move || a / b);
+        assert_eq!(div(7, 2), Some(3));
+        assert_eq!(div(7, 0), None);
+    }
+
+    #[test]
+    fn let_mut_typed() {
+        use super::option::{bind, ret};
+        let r = mdo! {
+            let mut count: i32 = 0;
+            x =<< Some(5);
+            let first = { count += x; count };
+            y =<< Some(3);
+            let second = { count += y; count };
+            ret ret((first, second))
+        };
+        assert_eq!(r, Some((5, 8)));
+    }
+
+    #[test]
+    fn result_inspect() {
+        use std::cell::RefCell;
+        use super::result::{bind, ret, inspect};
+        let log = &RefCell::new(vec![]);
+        let r: Result<i32, &str> = mdo! {
+            x =<< inspect(Ok(1), |v| log.borrow_mut().push(*v));
+            ign inspect(Err::<i32, _>("bad"), |v| log.borrow_mut().push(*v));
             ret ret(x)
-        }.collect::<Vec<_>>();
-        assert_eq!(l, vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+        };
+        assert_eq!(r, Err("bad"));
+        assert_eq!(*log.borrow(), vec![1]);
     }
 
     #[test]
-    fn ret_trick() {
-        use super::iter::bind;
-        let l = mdo! {
-            ret =<< 0..5;
-            ret 0..ret
-        }.collect::<Vec<_>>();
-        assert_eq!(l, vec![0, 0, 1, 0, 1, 2, 0, 1, 2, 3]);
+    fn iter_take_while_some() {
+        use super::iter::take_while_some;
+        let l = take_while_some(0u32.., |x| 100u32.checked_sub(x * x)).collect::<Vec<_>>();
+        assert_eq!(l, vec![100, 99, 96, 91, 84, 75, 64, 51, 36, 19, 0]);
     }
 
     #[test]
-    fn when_trick() {
-        use super::iter::{bind, ret, mzero};
-        let l = mdo! {
-            when =<< 0i32..5;
-            when when != 3;
-            ret ret(when)
-        }.collect::<Vec<_>>();
-        assert_eq!(l, vec![0, 1, 2, 4]);
+    fn some_if_ok_if() {
+        use std::cell::Cell;
+        use super::option::{some_if, some_if_else};
+        use super::result::ok_if;
+        assert_eq!(some_if(true, 1), Some(1));
+        assert_eq!(some_if(false, 1), None);
+        let calls = Cell::new(0);
+        let value = || { calls.set(calls.get() + 1); 2 };
+        assert_eq!(some_if_else(false, value), None);
+        assert_eq!(calls.get(), 0);
+        assert_eq!(some_if_else(true, value), Some(2));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(ok_if(true, 1, "e"), Ok(1));
+        assert_eq!(ok_if(false, 1, "e"), Err("e"));
+        assert_eq!(ok_if(true, (), ()), Ok(()));
+        assert_eq!(ok_if(false, (), ()), Err(()));
     }
 
     #[test]
-    fn ign_trick() {
-        use super::iter::{bind, ret};
+    fn result_chain_err() {
+        use std::error::Error;
+        use super::result::chain_err;
+        let parse = "x".parse::<i32>();
+        let r = chain_err("loading settings", chain_err("reading port", parse));
+        let e = r.unwrap_err();
+        assert_eq!(e.to_string(), "loading settings");
+        let mut messages = vec![];
+        let mut cur: Option<&dyn Error> = Some(&e);
+        while let Some(err) = cur {
+            messages.push(err.to_string());
+            cur = err.source();
+        }
+        assert_eq!(messages, vec!["loading settings",
+                                  "reading port",
+                                  "invalid digit found in string"]);
+        assert_eq!(e.cause.context, "reading port");
+        assert_eq!(chain_err("ctx", "1".parse::<i32>()).unwrap(), 1);
+    }
+
+    #[test]
+    fn iter_from_option_or() {
+        use super::iter::from_option_or;
+        assert_eq!(from_option_or(Some(1), 0).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(from_option_or(None, 0).collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn mdo_while_option() {
+        use super::option::{ret, mzero, while_m};
+        let fixed_point = mdo_while!(x = 1.0f64; ret((x.cos() - x).abs() > 1e-12); ret(x.cos()));
+        let x = fixed_point.unwrap();
+        assert!((x.cos() - x).abs() <= 1e-12);
+        assert!((x - 0.739085).abs() < 1e-6);
+        let r = mdo_while!(x = 1; ret(*x < 100); if x < 50 { ret(x * 3) } else { mzero() });
+        assert_eq!(r, None);
+        let r = mdo_while!(n = 0; ret(*n < 100_000); ret(n + 1));
+        assert_eq!(r, Some(100_000));
+    }
+
+    #[test]
+    fn mdo_while_iter() {
+        use super::iter::{ret, while_m};
+        let l = mdo_while!(x = 1; ret(*x < 8); vec![x * 2, x * 3]).collect::<Vec<_>>();
+        assert_eq!(l, vec![8, 12, 12, 18, 12, 18, 9]);
+        let halves = mdo_while!(x = 1000.0f64; ret(*x > 1.0); ret(x / 2.0)).collect::<Vec<_>>();
+        assert_eq!(halves.len(), 1);
+        assert!(halves[0] <= 1.0 && halves[0] > 0.5);
+    }
+
+    #[test]
+    fn result_zip() {
+        use super::result::zip;
+        assert_eq!(zip(Ok::<_, &str>(1), Ok('a')), Ok((1, 'a')));
+        assert_eq!(zip(Err::<i32, _>("a"), Ok('a')), Err("a"));
+        assert_eq!(zip(Ok(1), Err::<char, _>("b")), Err("b"));
+        assert_eq!(zip(Err::<i32, _>("a"), Err::<char, _>("b")), Err("a"));
+    }
+
+    #[test]
+    fn iter_chunks() {
+        use super::iter::chunks;
+        assert_eq!(chunks(0..5, 2).collect::<Vec<_>>(), vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert_eq!(chunks(0..4, 2).collect::<Vec<_>>(), vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(chunks(0..0, 2).count(), 0);
+    }
+
+    #[test]
+    fn option_zip3_5() {
+        use super::option::{zip3, zip4, zip5};
+        for mask in 0u8..8 {
+            let a = if mask & 1 == 0 { Some(1) } else { None };
+            let b = if mask & 2 == 0 { Some('b') } else { None };
+            let c = if mask & 4 == 0 { Some("c") } else { None };
+            let r = zip3(a, b, c);
+            if mask == 0 {
+                assert_eq!(r, Some((1, 'b', "c")));
+            } else {
+                assert_eq!(r, None);
+            }
+        }
+        assert_eq!(zip4(Some(1), Some(2), Some(3), Some(4)), Some((1, 2, 3, 4)));
+        assert_eq!(zip4(Some(1), Some(2), None::<i32>, Some(4)), None);
+        assert_eq!(zip5(Some(1), Some(2), Some(3), Some(4), Some(5)), Some((1, 2, 3, 4, 5)));
+        assert_eq!(zip5(Some(1), Some(2), Some(3), Some(4), None::<i32>), None);
+    }
+
+    #[test]
+    fn result_zip_all() {
+        use super::result::{zip3, zip4, zip5, zip_all, zip_all3, zip_all4, zip_all5};
+        #[derive(Debug, PartialEq)]
+        struct User { name: String, age: u8, email: String, id: u32 }
+        let field = |name: &'static str, ok: bool| if ok { Ok(name) } else { Err(name) };
+        let name = field("name", true).map(String::from);
+        let age = field("age", false).map(|_| 42u8);
+        let email = field("email", true).map(String::from);
+        let id = field("id", false).map(|_| 7u32);
+        assert_eq!(zip4(name.clone(), age, email.clone(), id), Err("age"));
+        assert_eq!(zip_all4(name, age, email, id), Err(vec!["age", "id"]));
+
+        let user = zip_all4(Ok::<_, &str>("bob".to_string()), Ok(30), Ok("b@o.b".to_string()), Ok(1))
+            .map(|(name, age, email, id)| User { name, age, email, id });
+        assert_eq!(user.map(|u| u.age), Ok(30));
+
+        assert_eq!(zip_all(Ok::<_, &str>(1), Ok(2)), Ok((1, 2)));
+        assert_eq!(zip_all::<i32, i32, _>(Err("a"), Err("b")), Err(vec!["a", "b"]));
+        assert_eq!(zip3(Ok::<_, &str>(1), Ok(2), Ok(3)), Ok((1, 2, 3)));
+        assert_eq!(zip_all3(Ok(1), Err::<i32, _>("b"), Ok(3)), Err(vec!["b"]));
+        assert_eq!(zip5(Ok(1), Ok(2), Ok(3), Err::<i32, _>("d"), Err::<i32, _>("e")), Err("d"));
+        assert_eq!(zip_all5(Ok(1), Ok(2), Ok(3), Err::<i32, _>("d"), Err::<i32, _>("e")),
+                   Err(vec!["d", "e"]));
+    }
+
+    #[test]
+    fn option_ret_ref() {
+        use super::option::{bind, ret_ref};
+        let words = vec!["monadic".to_string(), "do".to_string()];
+        let words = &words;
+        let r: Option<&str> = mdo! {
+            first =<< words.first();
+            rest =<< first.get(1..);
+            short =<< rest.split('a').next();
+            ret ret_ref(short)
+        };
+        assert_eq!(r, Some("on"));
+        assert_eq!(words.len(), 2);
+    }
+
+    #[test]
+    fn iter_repeat_n() {
+        use super::iter::{bind, ret, repeat_n};
+        assert_eq!(repeat_n('a', 3).collect::<String>(), "aaa");
         let l = mdo! {
-            ign =<< 0i32..5;
-            ign 0..0;
-            ret ret(ign)
+            x =<< 0..2;
+            ign repeat_n((), 3);
+            ret ret(x)
         }.collect::<Vec<_>>();
-        assert_eq!(l, vec![]);
+        assert_eq!(l, vec![0, 0, 0, 1, 1, 1]);
     }
 
     #[test]
-    fn mdo_doc_example() {
-        use super::iter::{bind, ret, mzero};
+    fn option_iter_opt_first_ok() {
+        use std::cell::Cell;
+        use super::option::{first_ok, iter_opt};
+        assert_eq!(iter_opt(vec![None, Some(2), Some(3)]), Some(2));
+        assert_eq!(iter_opt(Vec::<Option<i32>>::new()), None);
+        let calls = &Cell::new(0);
+        let choice = |x: Option<i32>| move || { calls.set(calls.get() + 1); x };
+        assert_eq!(first_ok(vec![choice(None), choice(Some(2)), choice(Some(3))]), Some(2));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(first_ok(vec![choice(None), choice(None)]), None);
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn iter_debug_enumerate_m() {
+        use super::iter::{bind, ret, mzero, debug_enumerate_m, trace_enumerate_m};
+        let mut lines = vec![];
+        let l = trace_enumerate_m("even", (0..6).filter(|x| x % 2 == 0), |s| lines.push(s.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 2, 4]);
+        assert_eq!(lines, vec!["even[0]: 0", "even[1]: 2", "even[2]: 4"]);
         let l = mdo! {
-            x: i32 =<< 0..5; // assign x to [0, 5[
-            ign 0..2; // duplicate each value
-            when x % 2 == 0; // filter on even values
-            let y = x + 5; // create y
-            ret ret(y + 5) // return y + 5
+            x =<< debug_enumerate_m("x", 0..4);
+            when x > 1;
+            ret ret(x)
         }.collect::<Vec<_>>();
-        assert_eq!(l, vec![10, 10, 12, 12, 14, 14]);
+        assert_eq!(l, vec![2, 3]);
+    }
+
+    #[test]
+    fn iter_bind_boxed() {
+        use std::iter;
+        use super::iter::bind_boxed;
+        let l = bind_boxed(0..5, |x| if x % 2 == 0 {
+            Box::new(0..x)
+        } else {
+            Box::new(iter::empty())
+        }).collect::<Vec<_>>();
+        assert_eq!(l, vec![0, 1, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn ensure_instruction() {
+        use super::result::{bind, ret, ensure};
+        #[derive(Debug, PartialEq)]
+        enum Error {
+            Negative(i32),
+            TooBig { value: i32, max: i32 },
+        }
+        let check = |x: i32| mdo! {
+            ensure x >= 0, Error::Negative(x);
+            let y = x * 10;
+            ensure y <= 100, Error::TooBig { value: y, max: 100 };
+            ret ret(y)
+        };
+        assert_eq!(check(5), Ok(50));
+        assert_eq!(check(-3), Err(Error::Negative(-3)));
+        assert_eq!(check(20), Err(Error::TooBig { value: 200, max: 100 }));
+    }
+
+    #[test]
+    fn option_all_some() {
+        use super::option::{all_some, iter_opt};
+        assert_eq!(all_some(vec![Some(1), Some(2)]), Some(vec![1, 2]));
+        assert_eq!(all_some(vec![Some(1), None, Some(3)]), None);
+        assert_eq!(all_some(Vec::<Option<i32>>::new()), Some(vec![]));
+        assert_eq!(iter_opt(vec![None, Some(2), None]), Some(2));
+        assert_eq!(iter_opt(vec![None::<i32>, None]), None);
+    }
+
+    #[test]
+    fn option_cache() {
+        use std::cell::Cell;
+        use super::option::{bind, ret, cache};
+        let calls = Cell::new(0);
+        let mut cell = None;
+        let mut run = |x: i32| {
+            let factor = cache(&mut cell, || { calls.set(calls.get() + 1); 10 });
+            mdo! {
+                y =<< x.checked_mul(factor);
+                ret ret(y + 1)
+            }
+        };
+        assert_eq!(run(1), Some(11));
+        assert_eq!(run(2), Some(21));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn iter_intersperse() {
+        use super::iter::intersperse;
+        assert_eq!(intersperse(0..3, 99).collect::<Vec<_>>(), vec![0, 99, 1, 99, 2]);
+        assert_eq!(intersperse(0..1, 99).collect::<Vec<_>>(), vec![0]);
+        assert_eq!(intersperse(0..0, 99).count(), 0);
+    }
+
+    #[test]
+    fn result_first_ok() {
+        use std::cell::Cell;
+        use super::result::{first_ok, sequence};
+        assert_eq!(first_ok(vec![Ok::<_, &str>(1), Ok(2)]), Ok(1));
+        assert_eq!(first_ok(vec![Err::<i32, _>("a"), Err("b")]), Err(vec!["a", "b"]));
+        assert_eq!(first_ok(Vec::<Result<i32, &str>>::new()), Err(vec![]));
+        let consumed = Cell::new(0);
+        let xs = vec![Err("a"), Ok(2), Ok(3), Err("d")];
+        let r = first_ok(xs.into_iter().inspect(|_| consumed.set(consumed.get() + 1)));
+        assert_eq!(r, Ok(2));
+        assert_eq!(consumed.get(), 2);
+
+        assert_eq!(sequence(vec![Ok::<_, &str>(1), Ok(2)]), Ok(vec![1, 2]));
+        assert_eq!(sequence(vec![Err::<i32, _>("a"), Err("b")]), Err("a"));
+        assert_eq!(sequence(vec![Ok(1), Err("b"), Ok(3)]), Err("b"));
+    }
+
+    #[test]
+    fn iter_concat_m() {
+        use super::iter::{bind, concat_m, concat_vec_m};
+        let nested = || vec![0..2, 5..5, 2..4].into_iter();
+        assert_eq!(concat_m(nested()).collect::<Vec<_>>(),
+                   bind(nested(), |x| x).collect::<Vec<_>>());
+        let v = concat_vec_m(vec![vec![1, 2], vec![], vec![3, 4, 5]]);
+        assert_eq!(v, vec![1, 2, 3, 4, 5]);
+        assert!(v.capacity() >= v.len());
+    }
+
+    #[test]
+    fn result_or_else_m() {
+        use super::result::or_else_m;
+        #[derive(Debug, PartialEq)]
+        struct Fatal(String);
+        let recover = |r: Result<i32, ::std::num::ParseIntError>| or_else_m(r, |e| {
+            if *e.kind() == ::std::num::IntErrorKind::Empty { Ok(0) } else { Err(Fatal(e.to_string())) }
+        });
+        assert_eq!(recover("4".parse()), Ok(4));
+        assert_eq!(recover("".parse()), Ok(0));
+        assert_eq!(recover("x".parse()), Err(Fatal("invalid digit found in string".to_string())));
+    }
+
+    #[test]
+    fn iter_bind_indexed() {
+        use super::iter::{bind_indexed, repeat_n};
+        let triangle = bind_indexed("abc".chars(), |i, c| repeat_n(c, i + 1))
+            .collect::<String>();
+        assert_eq!(triangle, "abbccc");
+    }
+
+    #[test]
+    fn iter_intersperse_with_m() {
+        use super::iter::{bind, ret, intersperse, intersperse_with_m};
+        let words = intersperse(mdo! {
+            w =<< vec!["a", "b", "c"];
+            ret ret(w.to_uppercase())
+        }, ",".to_string()).collect::<String>();
+        assert_eq!(words, "A,B,C");
+        let mut n = 0;
+        let l = intersperse_with_m(|| { n += 1; n * 100 }, 1..4).collect::<Vec<_>>();
+        assert_eq!(l, vec![1, 100, 2, 200, 3]);
+        assert_eq!(intersperse_with_m(|| 0, 1..2).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(intersperse_with_m(|| 0, 1..1).count(), 0);
     }
 }
@@ -0,0 +1,19 @@
+// Copyright (c) 2014 Guillaume Pinot <texitoi(a)texitoi.eu>
+//
+// This work is free. You can redistribute it and/or modify it under
+// the terms of the Do What The Fuck You Want To Public License,
+// Version 2, as published by Sam Hocevar. See the COPYING file for
+// more details.
+
+extern crate trybuild;
+
+// The expected errors in tests/ui are those of the stable compiler, set
+// MDO_SKIP_UI_TESTS to skip them on other toolchains.
+#[test]
+fn compile_fail() {
+    if std::env::var_os("MDO_SKIP_UI_TESTS").is_some() {
+        return;
+    }
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
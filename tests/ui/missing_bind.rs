@@ -0,0 +1,9 @@
+#[macro_use] extern crate mdo;
+
+fn main() {
+    use mdo::option::ret;
+    let _ = mdo! {
+        x =<< ret(5);
+        ret ret(x + 1)
+    };
+}
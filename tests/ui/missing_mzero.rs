@@ -0,0 +1,10 @@
+#[macro_use] extern crate mdo;
+
+fn main() {
+    use mdo::option::{bind, ret};
+    let _ = mdo! {
+        x =<< ret(5);
+        when x > 0;
+        ret ret(x + 1)
+    };
+}
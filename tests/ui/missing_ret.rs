@@ -0,0 +1,8 @@
+#[macro_use] extern crate mdo;
+
+fn main() {
+    use mdo::option::{bind, ret};
+    let _: Option<i32> = mdo! {
+        x =<< ret(5);
+    };
+}
@@ -0,0 +1,7 @@
+#[macro_use] extern crate mdo;
+
+fn main() {
+    let _: Result<i32, std::num::ParseIntError> = try_m! {
+        x: i32 =<< "5".parse();
+    };
+}